@@ -0,0 +1,5 @@
+pub mod obj;
+pub mod parse;
+pub mod ply;
+pub mod regina;
+pub mod snappy;
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use image::RgbImage;
+
+use crate::loaders::parse::{LoadError, ParseError, Token, Tokens};
+use crate::math::colliders::Collider;
+use crate::math::colors::Color;
+use crate::math::geometry::mesh::MeshGeometry;
+use crate::math::vectors::Vec3;
+use crate::rendering::materials::Material;
+use crate::rendering::textures::{SampleMode, Texture, TextureAtlas};
+
+/// A material read from a `.mtl` file. Only the fields which map onto the
+/// renderer's materials are kept.
+#[derive(Clone, Debug)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// `Kd`
+    pub diffuse: Color,
+    /// `Ks`
+    pub specular: Color,
+    /// `Ke`
+    pub emissive: Color,
+    /// `Ns`, from 0 (rough) up to around 1000 (polished).
+    pub shininess: f32,
+    /// `illum`. Models 3 and up turn on reflections.
+    pub illumination: u32,
+    /// The image named by `map_Kd`, once loaded.
+    pub diffuse_map: Option<RgbImage>,
+    /// The file name given to `map_Kd`, relative to the `.mtl` file.
+    pub diffuse_map_name: Option<String>,
+}
+
+impl ObjMaterial {
+    pub fn new(name: &str) -> ObjMaterial {
+        ObjMaterial {
+            name: name.to_string(),
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::zero(),
+            emissive: Color::zero(),
+            shininess: 0.0,
+            illumination: 2,
+            diffuse_map: None,
+            diffuse_map_name: None,
+        }
+    }
+
+    /// The renderer's nearest material: emissive if the material glows, metal
+    /// if it has reflections turned on and lambertian otherwise. The diffuse
+    /// map, if there is one, is added to `atlas`.
+    pub fn material(&self, atlas: &mut TextureAtlas) -> Material {
+        let glow = self.emissive.r.max(self.emissive.g).max(self.emissive.b);
+        if glow > 0.0 {
+            return Material::Emissive {
+                texture: atlas.add(Texture::Constant(self.emissive / glow)),
+                amplify: glow,
+            };
+        }
+        if self.illumination >= 3 {
+            return Material::Metal {
+                albedo: atlas.add(Texture::Constant(self.specular)),
+                fuzziness: (2.0 / (self.shininess + 2.0)).sqrt(),
+            };
+        }
+        let albedo = match self.diffuse_map {
+            Some(ref image) => atlas.add(Texture::Image(image.clone(), SampleMode::Wrap)),
+            None => atlas.add(Texture::Constant(self.diffuse)),
+        };
+        Material::Lambertian { albedo }
+    }
+}
+
+/// The faces of an `.obj` file using one material, as a single mesh.
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    pub material: Option<String>,
+    pub mesh: MeshGeometry,
+}
+
+/// A model read from a Wavefront `.obj` file, along with the materials of
+/// the `.mtl` libraries it names.
+#[derive(Clone, Debug)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    pub material_libraries: Vec<String>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl ObjModel {
+    /// A collider and material for each mesh. Meshes whose material is not
+    /// in any library are given the default one.
+    pub fn renderables(&self, atlas: &mut TextureAtlas) -> Vec<(Collider, Material)> {
+        let default = ObjMaterial::new("default");
+        self.meshes
+            .iter()
+            .map(|mesh| {
+                let material = mesh
+                    .material
+                    .as_ref()
+                    .and_then(|name| {
+                        self.materials
+                            .iter()
+                            .find(|material| &material.name == name)
+                    })
+                    .unwrap_or(&default);
+                (mesh.mesh.clone().into(), material.material(atlas))
+            })
+            .collect()
+    }
+}
+
+/// Read an `.obj` file along with its material libraries and their diffuse
+/// maps, which are looked for next to the files naming them.
+pub fn load(path: &Path) -> Result<ObjModel, LoadError> {
+    let text = std::fs::read_to_string(path)?;
+    let mut model = parse(&text)?;
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+    for library in &model.material_libraries {
+        let library_path = directory.join(library);
+        let text = std::fs::read_to_string(&library_path)?;
+        let library_directory = library_path.parent().unwrap_or(directory);
+        for mut material in parse_mtl(&text)? {
+            if let Some(ref name) = material.diffuse_map_name {
+                material.diffuse_map = Some(image::open(library_directory.join(name))?.to_rgb());
+            }
+            model.materials.push(material);
+        }
+    }
+    Ok(model)
+}
+
+/// The token after `keyword` on its line, or an error naming `what` was
+/// expected there.
+fn argument<'a>(
+    tokens: &mut Tokens<'a>,
+    keyword: &Token,
+    what: &str,
+) -> Result<Token<'a>, ParseError> {
+    tokens
+        .on_line(keyword)
+        .ok_or_else(|| keyword.error(format!("expected {} after `{}`", what, keyword.text)))
+}
+
+fn skip_line(tokens: &mut Tokens, keyword: &Token) {
+    while tokens.on_line(keyword).is_some() {}
+}
+
+fn read_vector(tokens: &mut Tokens, keyword: &Token, what: &str) -> Result<Vec3, ParseError> {
+    let x = argument(tokens, keyword, what)?.parse(what)?;
+    let y = argument(tokens, keyword, what)?.parse(what)?;
+    let z = argument(tokens, keyword, what)?.parse(what)?;
+    Ok(Vec3::new(x, y, z))
+}
+
+fn read_color(tokens: &mut Tokens, keyword: &Token) -> Result<Color, ParseError> {
+    let color = read_vector(tokens, keyword, "a color component")?;
+    Ok(Color::new(color.x, color.y, color.z))
+}
+
+/// Turn an index of an `.obj` face, which counts from 1 or back from the
+/// end when negative, into an index from 0.
+fn resolve(token: &Token, text: &str, count: usize, what: &str) -> Result<usize, ParseError> {
+    let index: i64 = text
+        .parse()
+        .map_err(|_| token.error(format!("expected {} index", what)))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(token.error(format!("{} index out of range", what)));
+    }
+    Ok(resolved as usize)
+}
+
+/// A mesh being gathered up from the faces using one material. Corners are
+/// shared between faces when they use the same position, texture coordinates
+/// and normal.
+struct MeshBuilder {
+    material: Option<String>,
+    corners: HashMap<(usize, Option<usize>, Option<usize>), usize>,
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(f32, f32)>,
+    triangles: Vec<[usize; 3]>,
+    all_normals: bool,
+    all_uvs: bool,
+}
+
+impl MeshBuilder {
+    fn new(material: Option<String>) -> MeshBuilder {
+        MeshBuilder {
+            material,
+            corners: HashMap::new(),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            triangles: Vec::new(),
+            all_normals: true,
+            all_uvs: true,
+        }
+    }
+
+    fn finish(self) -> Option<ObjMesh> {
+        if self.triangles.is_empty() {
+            return None;
+        }
+        let mut mesh = MeshGeometry::new(self.positions, self.triangles);
+        if self.all_normals {
+            mesh = mesh.with_normals(self.normals);
+        }
+        if self.all_uvs {
+            mesh = mesh.with_uvs(self.uvs);
+        }
+        Some(ObjMesh {
+            material: self.material,
+            mesh,
+        })
+    }
+}
+
+/// Parse the text of an `.obj` file. Polygons are split into fans of
+/// triangles, and a separate mesh is made for each material used. Lines,
+/// points, groups and statements this does not know are skipped, but a
+/// statement it does know which cannot be read is an error.
+pub fn parse(text: &str) -> Result<ObjModel, ParseError> {
+    let mut tokens = Tokens::new(text, Some("#"));
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<(f32, f32)> = Vec::new();
+    let mut material_libraries = Vec::new();
+    let mut finished = Vec::new();
+    let mut builder = MeshBuilder::new(None);
+
+    while !tokens.finished() {
+        let keyword = tokens.expect("a statement")?;
+        match keyword.text {
+            "v" => {
+                positions.push(read_vector(&mut tokens, &keyword, "a coordinate")?);
+                // An optional weight.
+                skip_line(&mut tokens, &keyword);
+            }
+            "vn" => {
+                let normal = read_vector(&mut tokens, &keyword, "a normal component")?;
+                if normal.length() == 0.0 {
+                    return Err(keyword.error("a normal cannot be zero"));
+                }
+                normals.push(normal.normalized());
+            }
+            "vt" => {
+                let u = argument(&mut tokens, &keyword, "a texture coordinate")?
+                    .parse("a texture coordinate")?;
+                let v = match tokens.on_line(&keyword) {
+                    Some(token) => token.parse("a texture coordinate")?,
+                    None => 0.0,
+                };
+                uvs.push((u, v));
+                skip_line(&mut tokens, &keyword);
+            }
+            "f" => {
+                let mut corners = Vec::new();
+                while let Some(token) = tokens.on_line(&keyword) {
+                    let mut parts = token.text.split('/');
+                    let position = resolve(
+                        &token,
+                        parts.next().unwrap_or(""),
+                        positions.len(),
+                        "a vertex",
+                    )?;
+                    let uv = match parts.next() {
+                        Some(part) if !part.is_empty() => {
+                            Some(resolve(&token, part, uvs.len(), "a texture coordinate")?)
+                        }
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(part) if !part.is_empty() => {
+                            Some(resolve(&token, part, normals.len(), "a normal")?)
+                        }
+                        _ => None,
+                    };
+                    if parts.next().is_some() {
+                        return Err(token.error("too many parts to a face corner"));
+                    }
+                    let key = (position, uv, normal);
+                    let next = builder.positions.len();
+                    let corner = *builder.corners.entry(key).or_insert(next);
+                    if corner == next {
+                        builder.positions.push(positions[position]);
+                        builder.all_normals &= normal.is_some();
+                        builder.all_uvs &= uv.is_some();
+                        builder
+                            .normals
+                            .push(normal.map_or(Vec3::zero(), |normal| normals[normal]));
+                        builder.uvs.push(uv.map_or((0.0, 0.0), |uv| uvs[uv]));
+                    }
+                    corners.push(corner);
+                }
+                if corners.len() < 3 {
+                    return Err(keyword.error("a face needs at least three corners"));
+                }
+                for index in 1..corners.len() - 1 {
+                    builder
+                        .triangles
+                        .push([corners[0], corners[index], corners[index + 1]]);
+                }
+            }
+            "usemtl" => {
+                let name = argument(&mut tokens, &keyword, "a material name")?.text;
+                let previous =
+                    std::mem::replace(&mut builder, MeshBuilder::new(Some(name.to_string())));
+                finished.extend(previous.finish());
+            }
+            "mtllib" => {
+                while let Some(library) = tokens.on_line(&keyword) {
+                    material_libraries.push(library.text.to_string());
+                }
+            }
+            // Objects, groups, smoothing groups, lines, points and anything
+            // newer are skipped.
+            _ => skip_line(&mut tokens, &keyword),
+        }
+    }
+    finished.extend(builder.finish());
+
+    Ok(ObjModel {
+        meshes: finished,
+        material_libraries,
+        materials: Vec::new(),
+    })
+}
+
+/// Parse the text of a `.mtl` material library. Fields with no use are
+/// skipped, and diffuse maps are named but not loaded.
+pub fn parse_mtl(text: &str) -> Result<Vec<ObjMaterial>, ParseError> {
+    let mut tokens = Tokens::new(text, Some("#"));
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    while !tokens.finished() {
+        let keyword = tokens.expect("a statement")?;
+        if keyword.text == "newmtl" {
+            let name = argument(&mut tokens, &keyword, "a material name")?.text;
+            materials.push(ObjMaterial::new(name));
+            continue;
+        }
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(keyword.error("expected newmtl before any material fields")),
+        };
+        match keyword.text {
+            "Kd" => material.diffuse = read_color(&mut tokens, &keyword)?,
+            "Ks" => material.specular = read_color(&mut tokens, &keyword)?,
+            "Ke" => material.emissive = read_color(&mut tokens, &keyword)?,
+            "Ns" => {
+                material.shininess =
+                    argument(&mut tokens, &keyword, "a shininess")?.parse("a shininess")?
+            }
+            "illum" => {
+                material.illumination = argument(&mut tokens, &keyword, "an illumination model")?
+                    .parse("an illumination model")?
+            }
+            "map_Kd" => {
+                // Options come before the file name, which is last.
+                let mut name = argument(&mut tokens, &keyword, "a file name")?;
+                while let Some(token) = tokens.on_line(&keyword) {
+                    name = token;
+                }
+                material.diffuse_map_name = Some(name.text.to_string());
+            }
+            // Ambient color, transparency, refraction and the other maps
+            // have nothing to map onto.
+            _ => skip_line(&mut tokens, &keyword),
+        }
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_run_to_the_end_of_the_line() {
+        let text = "# a triangle\nv 0 0 0 # origin\nv 1 0 0\nv 0 1 0\nf 1 2 3 # the face\n";
+        let model = parse(text).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].mesh.positions.len(), 3);
+        assert_eq!(model.meshes[0].mesh.triangles, vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn faces_are_fanned_and_split_by_material() {
+        let text = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+                    usemtl red\nf 1 2 3 4\nusemtl blue\nf -4 -2 -1\n";
+        let model = parse(text).unwrap();
+        let materials: Vec<_> = model
+            .meshes
+            .iter()
+            .map(|mesh| mesh.material.clone())
+            .collect();
+        assert_eq!(
+            materials,
+            vec![Some("red".to_string()), Some("blue".to_string())]
+        );
+        assert_eq!(model.meshes[0].mesh.triangles, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(model.meshes[1].mesh.triangles.len(), 1);
+    }
+
+    #[test]
+    fn zero_normals_are_rejected() {
+        let error = parse("v 0 0 0\nvn 0 0 0\n").unwrap_err();
+        assert_eq!((error.line, error.column), (2, 1));
+        assert_eq!(error.token, "vn");
+    }
+
+    #[test]
+    fn errors_point_at_the_bad_token() {
+        let error = parse("v 0 0 0\n\nv 1 x 0\n").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 5));
+        assert_eq!(error.token, "x");
+
+        let error = parse("v 0 0 0\nv 1 0 0\n  f 1 2 3\n").unwrap_err();
+        assert_eq!((error.line, error.column), (3, 9));
+        assert_eq!(error.token, "3");
+
+        let error = parse("v 0 0 0\nf 1 1\n").unwrap_err();
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn materials_are_read() {
+        let text = "newmtl glow # bright\nKe 2 1 0\nnewmtl plain\nKd 0.1 0.2 0.3\n";
+        let materials = parse_mtl(text).unwrap();
+        assert_eq!(materials.len(), 2);
+        assert_eq!(materials[0].name, "glow");
+        assert_eq!(materials[0].emissive.r, 2.0);
+        assert_eq!(materials[1].diffuse.b, 0.3);
+        assert_eq!(parse_mtl("Kd 1 1 1\n").unwrap_err().line, 1);
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// An error in a text file, pointing at the token which could not be read.
+/// Lines and columns count from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(f, "{}:{}: {}", self.line, self.column, self.message)
+        } else {
+            write!(
+                f,
+                "{}:{}: {} (at `{}`)",
+                self.line, self.column, self.message, self.token
+            )
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Anything which can go wrong reading a file from disk.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    Parse(ParseError),
+    Image(image::ImageError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "{}", error),
+            LoadError::Parse(error) => write!(f, "{}", error),
+            LoadError::Image(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<std::io::Error> for LoadError {
+    fn from(error: std::io::Error) -> LoadError {
+        LoadError::Io(error)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(error: ParseError) -> LoadError {
+        LoadError::Parse(error)
+    }
+}
+
+impl From<image::ImageError> for LoadError {
+    fn from(error: image::ImageError) -> LoadError {
+        LoadError::Image(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Token<'a> {
+    pub text: &'a str,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl<'a> Token<'a> {
+    pub fn error<S: Into<String>>(&self, message: S) -> ParseError {
+        ParseError {
+            line: self.line,
+            column: self.column,
+            token: self.text.to_string(),
+            message: message.into(),
+        }
+    }
+
+    /// Parse the token as a number (or anything else with `FromStr`), naming
+    /// what was expected if it is not one.
+    pub fn parse<T: FromStr>(&self, what: &str) -> Result<T, ParseError> {
+        self.text
+            .parse()
+            .map_err(|_| self.error(format!("expected {}", what)))
+    }
+}
+
+/// Splits text into whitespace separated tokens, remembering where each one
+/// came from. Everything from `comment`, if there is one, to the end of its
+/// line is skipped.
+pub struct Tokens<'a> {
+    tokens: Vec<Token<'a>>,
+    next: usize,
+    end_line: usize,
+}
+
+impl<'a> Tokens<'a> {
+    pub fn new(text: &'a str, comment: Option<&str>) -> Tokens<'a> {
+        let mut tokens = Vec::new();
+        let mut end_line = 1;
+        for (index, line) in text.lines().enumerate() {
+            end_line = index + 1;
+            let line = match comment.and_then(|comment| line.find(comment)) {
+                Some(start) => &line[..start],
+                None => line,
+            };
+            let mut rest = line;
+            while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+                let token = &rest[start..];
+                let length = token.find(char::is_whitespace).unwrap_or(token.len());
+                tokens.push(Token {
+                    text: &token[..length],
+                    line: index + 1,
+                    column: line.len() - token.len() + 1,
+                });
+                rest = &token[length..];
+            }
+        }
+        Tokens {
+            tokens,
+            next: 0,
+            end_line,
+        }
+    }
+
+    pub fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.next).copied()
+    }
+
+    /// The next token, or an error saying `what` was expected if the text
+    /// has run out.
+    pub fn expect(&mut self, what: &str) -> Result<Token<'a>, ParseError> {
+        let token = self.peek().ok_or_else(|| ParseError {
+            line: self.end_line,
+            column: 1,
+            token: String::new(),
+            message: format!("expected {} but reached the end of the file", what),
+        })?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    pub fn parse<T: FromStr>(&mut self, what: &str) -> Result<T, ParseError> {
+        self.expect(what)?.parse(what)
+    }
+
+    /// Take the next token only if it is on the same line as `previous`.
+    pub fn on_line(&mut self, previous: &Token) -> Option<Token<'a>> {
+        let token = self.peek().filter(|token| token.line == previous.line)?;
+        self.next += 1;
+        Some(token)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.tokens.len()
+    }
+}
@@ -0,0 +1,585 @@
+use std::path::Path;
+
+use crate::loaders::parse::{LoadError, ParseError, Token, Tokens};
+use crate::math::colliders::Collider;
+use crate::math::colors::Color;
+use crate::math::geometry::mesh::MeshGeometry;
+use crate::math::vectors::Vec3;
+use crate::rendering::materials::Material;
+use crate::rendering::textures::{Texture, TextureAtlas, TriangleLayout};
+
+/// A triangle mesh read from a `.ply` file. When the vertices have colors,
+/// every triangle gets corners of its own, with texture coordinates laid out
+/// by a `TriangleLayout` so the colors can be looked up from a hit.
+#[derive(Clone, Debug)]
+pub struct PlyModel {
+    pub mesh: MeshGeometry,
+    /// The colors of the corners of each triangle, if the vertices had any.
+    pub colors: Option<Vec<[Color; 3]>>,
+}
+
+impl PlyModel {
+    /// A collider for the mesh and a lambertian material, colored by the
+    /// vertex colors if there are any and light grey otherwise.
+    pub fn renderable(&self, atlas: &mut TextureAtlas) -> (Collider, Material) {
+        let albedo = match self.colors {
+            Some(ref colors) => atlas.add(Texture::VertexColors(
+                TriangleLayout::new(colors.len()),
+                colors.clone(),
+            )),
+            None => atlas.add(Texture::Constant(Color::new(0.8, 0.8, 0.8))),
+        };
+        (self.mesh.clone().into(), Material::Lambertian { albedo })
+    }
+}
+
+pub fn load(path: &Path) -> Result<PlyModel, LoadError> {
+    let bytes = std::fs::read(path)?;
+    Ok(parse(&bytes)?)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// The types a property can be stored as.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Scalar {
+    Char,
+    UChar,
+    Short,
+    UShort,
+    Int,
+    UInt,
+    Float,
+    Double,
+}
+
+impl Scalar {
+    fn named(token: &Token) -> Result<Scalar, ParseError> {
+        use Scalar::*;
+        Ok(match token.text {
+            "char" | "int8" => Char,
+            "uchar" | "uint8" => UChar,
+            "short" | "int16" => Short,
+            "ushort" | "uint16" => UShort,
+            "int" | "int32" => Int,
+            "uint" | "uint32" => UInt,
+            "float" | "float32" => Float,
+            "double" | "float64" => Double,
+            _ => return Err(token.error("expected a property type")),
+        })
+    }
+
+    fn size(self) -> usize {
+        use Scalar::*;
+        match self {
+            Char | UChar => 1,
+            Short | UShort => 2,
+            Int | UInt | Float => 4,
+            Double => 8,
+        }
+    }
+
+    /// Read a little endian value from the first `size` bytes.
+    fn read(self, bytes: &[u8]) -> f64 {
+        use Scalar::*;
+        match self {
+            Char => f64::from(bytes[0] as i8),
+            UChar => f64::from(bytes[0]),
+            Short => f64::from(i16::from_le_bytes([bytes[0], bytes[1]])),
+            UShort => f64::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+            Int => f64::from(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            UInt => f64::from(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            Float => f64::from(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+            Double => {
+                let mut eight = [0; 8];
+                eight.copy_from_slice(&bytes[..8]);
+                f64::from_le_bytes(eight)
+            }
+        }
+    }
+
+    /// The value standing for full brightness when a color is stored as
+    /// this type. Colors stored as integers use the whole range.
+    fn full(self) -> f64 {
+        use Scalar::*;
+        match self {
+            Char => f64::from(i8::MAX),
+            UChar => f64::from(u8::MAX),
+            Short => f64::from(i16::MAX),
+            UShort => f64::from(u16::MAX),
+            Int => f64::from(i32::MAX),
+            UInt => f64::from(u32::MAX),
+            Float | Double => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum PropertyKind {
+    Scalar(Scalar),
+    /// The type of the length, then the type of the items.
+    List(Scalar, Scalar),
+}
+
+#[derive(Clone, Debug)]
+struct Property<'a> {
+    name: &'a str,
+    kind: PropertyKind,
+}
+
+#[derive(Clone, Debug)]
+struct Element<'a> {
+    /// The `element` keyword, which errors in the element's data point at.
+    token: Token<'a>,
+    name: &'a str,
+    count: usize,
+    properties: Vec<Property<'a>>,
+}
+
+impl<'a> Element<'a> {
+    fn find(&self, names: &[&str]) -> Option<usize> {
+        self.properties
+            .iter()
+            .position(|property| names.contains(&property.name))
+    }
+
+    fn scalar(&self, property: usize) -> Result<Scalar, ParseError> {
+        match self.properties[property].kind {
+            PropertyKind::Scalar(scalar) => Ok(scalar),
+            PropertyKind::List(..) => Err(self.token.error(format!(
+                "expected `{}` of {} to be a single value, not a list",
+                self.properties[property].name, self.name
+            ))),
+        }
+    }
+}
+
+/// Where the values of the elements are read from.
+enum Body<'a> {
+    Ascii(Tokens<'a>),
+    Binary { bytes: &'a [u8], offset: usize },
+}
+
+impl<'a> Body<'a> {
+    fn read(&mut self, scalar: Scalar, element: &Element) -> Result<f64, ParseError> {
+        match self {
+            Body::Ascii(tokens) => tokens.parse(&format!("a value of {}", element.name)),
+            Body::Binary { bytes, offset } => {
+                let end = *offset + scalar.size();
+                if end > bytes.len() {
+                    return Err(element.token.error(format!(
+                        "the data ends partway through the {} elements",
+                        element.name
+                    )));
+                }
+                let value = scalar.read(&bytes[*offset..end]);
+                *offset = end;
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Where the header ends, as the offset of the line after `end_header`.
+fn header_end(bytes: &[u8]) -> Option<usize> {
+    let mut start = 0;
+    while start < bytes.len() {
+        let end = bytes[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |end| start + end);
+        if bytes[start..end].trim_ascii() == b"end_header" {
+            return Some((end + 1).min(bytes.len()));
+        }
+        start = end + 1;
+    }
+    None
+}
+
+/// Read the header up to and including `end_header`.
+fn parse_header<'a>(tokens: &mut Tokens<'a>) -> Result<(Format, Vec<Element<'a>>), ParseError> {
+    let magic = tokens.expect("`ply`")?;
+    if magic.text != "ply" {
+        return Err(magic.error("expected `ply`"));
+    }
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    loop {
+        let keyword = tokens.expect("`end_header`")?;
+        match keyword.text {
+            "format" => {
+                let name = tokens.expect("a format")?;
+                format = Some(match name.text {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => {
+                        return Err(name.error("big endian files are not supported"))
+                    }
+                    _ => return Err(name.error("expected a format")),
+                });
+                let _version = tokens.expect("a format version")?;
+            }
+            "element" => {
+                let name = tokens.expect("an element name")?.text;
+                let count = tokens.parse("an element count")?;
+                elements.push(Element {
+                    token: keyword,
+                    name,
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            "property" => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| keyword.error("expected an element before any properties"))?;
+                let type_token = tokens.expect("a property type")?;
+                let kind = if type_token.text == "list" {
+                    let length = Scalar::named(&tokens.expect("a list length type")?)?;
+                    let item = Scalar::named(&tokens.expect("a list item type")?)?;
+                    PropertyKind::List(length, item)
+                } else {
+                    PropertyKind::Scalar(Scalar::named(&type_token)?)
+                };
+                let name = tokens.expect("a property name")?.text;
+                element.properties.push(Property { name, kind });
+            }
+            "comment" | "obj_info" => while tokens.on_line(&keyword).is_some() {},
+            "end_header" => break,
+            _ => return Err(keyword.error("expected a header statement")),
+        }
+    }
+    let format = format.ok_or_else(|| magic.error("expected a format line in the header"))?;
+    Ok((format, elements))
+}
+
+/// Parse a `.ply` file, in either its ascii or binary little endian form.
+/// Vertices need positions and may have normals, texture coordinates and
+/// colors. Faces are split into fans of triangles. Other elements and
+/// properties are read past and dropped.
+pub fn parse(bytes: &[u8]) -> Result<PlyModel, ParseError> {
+    let not_text = |what: &str| ParseError {
+        line: 1,
+        column: 1,
+        token: String::new(),
+        message: format!("expected {} to be text", what),
+    };
+    let end = header_end(bytes).ok_or_else(|| ParseError {
+        line: 1,
+        column: 1,
+        token: String::new(),
+        message: "expected a header ending in `end_header`".to_string(),
+    })?;
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| not_text("the header"))?;
+    let mut tokens = Tokens::new(header, None);
+    let (format, elements) = parse_header(&mut tokens)?;
+    let mut body = match format {
+        Format::Ascii => {
+            // Read the whole file again so data lines keep their numbers.
+            let text = std::str::from_utf8(bytes).map_err(|_| not_text("an ascii file"))?;
+            let mut tokens = Tokens::new(text, None);
+            let header_lines = header.lines().count();
+            while tokens
+                .peek()
+                .is_some_and(|token| token.line <= header_lines)
+            {
+                tokens.expect("the header")?;
+            }
+            Body::Ascii(tokens)
+        }
+        Format::BinaryLittleEndian => Body::Binary { bytes, offset: end },
+    };
+
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
+    let mut colors = Vec::new();
+    // Faces keep the element they came from to point errors at.
+    let mut faces: Vec<(usize, Vec<usize>)> = Vec::new();
+    for (element_index, element) in elements.iter().enumerate() {
+        let mut values = vec![0.0; element.properties.len()];
+        let mut lists = vec![Vec::new(); element.properties.len()];
+        let read = |names: &[&str]| element.find(names);
+        let position = [read(&["x"]), read(&["y"]), read(&["z"])];
+        let normal = [read(&["nx"]), read(&["ny"]), read(&["nz"])];
+        let uv = [
+            read(&["s", "u", "texture_u"]),
+            read(&["t", "v", "texture_v"]),
+        ];
+        let color = [
+            read(&["red", "diffuse_red"]),
+            read(&["green", "diffuse_green"]),
+            read(&["blue", "diffuse_blue"]),
+        ];
+        let indices = read(&["vertex_indices", "vertex_index"]);
+        let is_vertex = element.name == "vertex";
+        let is_face = element.name == "face";
+        if is_vertex && position.iter().any(Option::is_none) {
+            return Err(element.token.error("expected vertices to have x, y and z"));
+        }
+        if is_face && indices.is_none() {
+            return Err(element.token.error("expected faces to have vertex_indices"));
+        }
+        let mut full = [1.0; 3];
+        if is_vertex {
+            for &property in position.iter().chain(&normal).chain(&uv).flatten() {
+                element.scalar(property)?;
+            }
+            for (full, &property) in full.iter_mut().zip(&color) {
+                if let Some(property) = property {
+                    *full = element.scalar(property)?.full();
+                }
+            }
+        }
+
+        for _ in 0..element.count {
+            for (index, property) in element.properties.iter().enumerate() {
+                match property.kind {
+                    PropertyKind::Scalar(scalar) => values[index] = body.read(scalar, element)?,
+                    PropertyKind::List(length, item) => {
+                        let length = body.read(length, element)?;
+                        lists[index].clear();
+                        for _ in 0..length.max(0.0) as usize {
+                            lists[index].push(body.read(item, element)?);
+                        }
+                    }
+                }
+            }
+            if is_vertex {
+                let vector = |[x, y, z]: [Option<usize>; 3]| {
+                    let [x, y, z] = [x, y, z].map(|index| values[index.unwrap()] as f32);
+                    Vec3::new(x, y, z)
+                };
+                positions.push(vector(position));
+                if normal.iter().all(Option::is_some) {
+                    let normal = vector(normal);
+                    if normal.length() == 0.0 {
+                        return Err(element
+                            .token
+                            .error(format!("vertex {} has a zero normal", positions.len() - 1)));
+                    }
+                    normals.push(normal.normalized());
+                }
+                if let [Some(u), Some(v)] = uv {
+                    uvs.push((values[u] as f32, values[v] as f32));
+                }
+                if color.iter().all(Option::is_some) {
+                    let [r, g, b] = [0, 1, 2]
+                        .map(|channel| (values[color[channel].unwrap()] / full[channel]) as f32);
+                    colors.push(Color::new(r, g, b));
+                }
+            } else if is_face {
+                let corners = lists[indices.unwrap()]
+                    .iter()
+                    .map(|&index| index as usize)
+                    .collect();
+                faces.push((element_index, corners));
+            }
+        }
+    }
+
+    let mut triangles = Vec::new();
+    for (face, (element, corners)) in faces.iter().enumerate() {
+        let token = &elements[*element].token;
+        if corners.len() < 3 {
+            return Err(token.error(format!("face {} has fewer than three corners", face)));
+        }
+        if let Some(corner) = corners.iter().find(|&&corner| corner >= positions.len()) {
+            return Err(token.error(format!(
+                "face {} uses vertex {}, but there are only {}",
+                face,
+                corner,
+                positions.len()
+            )));
+        }
+        for index in 1..corners.len() - 1 {
+            triangles.push([corners[0], corners[index], corners[index + 1]]);
+        }
+    }
+
+    if colors.is_empty() {
+        let mut mesh = MeshGeometry::new(positions, triangles);
+        if !normals.is_empty() {
+            mesh = mesh.with_normals(normals);
+        }
+        if !uvs.is_empty() {
+            mesh = mesh.with_uvs(uvs);
+        }
+        return Ok(PlyModel { mesh, colors: None });
+    }
+
+    // Each triangle needs its own corners to give them the texture
+    // coordinates of its cell of the layout.
+    let layout = TriangleLayout::new(triangles.len());
+    let corners = triangles
+        .iter()
+        .flat_map(|triangle| triangle.iter().copied());
+    let split_positions = corners.clone().map(|corner| positions[corner]).collect();
+    let split_normals: Vec<Vec3> = if normals.is_empty() {
+        Vec::new()
+    } else {
+        corners.map(|corner| normals[corner]).collect()
+    };
+    let split_uvs = (0..3 * triangles.len())
+        .map(|corner| layout.corner_uv(corner / 3, corner % 3))
+        .collect();
+    let split_triangles = (0..triangles.len())
+        .map(|triangle| [3 * triangle, 3 * triangle + 1, 3 * triangle + 2])
+        .collect();
+    let triangle_colors = triangles
+        .iter()
+        .map(|triangle| triangle.map(|corner| colors[corner]))
+        .collect();
+    let mut mesh = MeshGeometry::new(split_positions, split_triangles).with_uvs(split_uvs);
+    if !split_normals.is_empty() {
+        mesh = mesh.with_normals(split_normals);
+    }
+    Ok(PlyModel {
+        mesh,
+        colors: Some(triangle_colors),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "ply
+format {} 1.0
+comment a square and a triangle
+element vertex 5
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 2
+property list uchar int vertex_indices
+end_header
+";
+
+    const VERTICES: [([f32; 3], [u8; 3]); 5] = [
+        ([0.0, 0.0, 0.0], [255, 0, 0]),
+        ([1.0, 0.0, 0.0], [0, 255, 0]),
+        ([1.0, 1.0, 0.0], [0, 0, 255]),
+        ([0.0, 1.0, 0.0], [255, 255, 255]),
+        ([0.5, 0.5, 1.0], [0, 0, 0]),
+    ];
+
+    const FACES: [&[i32]; 2] = [&[0, 1, 2, 3], &[0, 1, 4]];
+
+    fn ascii() -> String {
+        let mut text = HEADER.replace("{}", "ascii");
+        for ([x, y, z], [r, g, b]) in &VERTICES {
+            text += &format!("{} {} {} {} {} {}\n", x, y, z, r, g, b);
+        }
+        for face in &FACES {
+            let corners: Vec<String> = face.iter().map(i32::to_string).collect();
+            text += &format!("{} {}\n", face.len(), corners.join(" "));
+        }
+        text
+    }
+
+    fn binary() -> Vec<u8> {
+        let mut bytes = HEADER.replace("{}", "binary_little_endian").into_bytes();
+        for (position, color) in &VERTICES {
+            for coordinate in position {
+                bytes.extend_from_slice(&coordinate.to_le_bytes());
+            }
+            bytes.extend_from_slice(color);
+        }
+        for face in &FACES {
+            bytes.push(face.len() as u8);
+            for corner in face.iter() {
+                bytes.extend_from_slice(&corner.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn ascii_and_binary_agree() {
+        let from_ascii = parse(ascii().as_bytes()).unwrap();
+        let from_binary = parse(&binary()).unwrap();
+        for model in &[&from_ascii, &from_binary] {
+            // The square is split in two, and every triangle gets its own
+            // corners for the vertex colors.
+            assert_eq!(model.mesh.triangles.len(), 3);
+            assert_eq!(model.mesh.positions.len(), 9);
+        }
+        let positions = |model: &PlyModel| -> Vec<[f32; 3]> {
+            let positions = model.mesh.positions.iter();
+            positions.map(|p| [p.x, p.y, p.z]).collect()
+        };
+        assert_eq!(positions(&from_ascii), positions(&from_binary));
+        assert_eq!(from_ascii.mesh.triangles, from_binary.mesh.triangles);
+        assert_eq!(from_ascii.mesh.uvs, from_binary.mesh.uvs);
+        let colors = |model: &PlyModel| -> Vec<[f32; 3]> {
+            let colors = model.colors.as_ref().unwrap();
+            colors
+                .iter()
+                .flatten()
+                .map(|color| [color.r, color.g, color.b])
+                .collect()
+        };
+        assert_eq!(colors(&from_ascii), colors(&from_binary));
+        assert_eq!(colors(&from_ascii)[2], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn errors_point_at_the_bad_line() {
+        let text = ascii().replace("1 0 0 0 255 0", "1 0 zero 0 255 0");
+        let error = parse(text.as_bytes()).unwrap_err();
+        assert_eq!((error.line, error.column), (15, 5));
+        assert_eq!(error.token, "zero");
+
+        let text = ascii().replace("property uchar red", "property colour red");
+        let error = parse(text.as_bytes()).unwrap_err();
+        assert_eq!((error.line, error.column), (8, 10));
+
+        // Face errors point at the face element.
+        let text = ascii().replace("3 0 1 4", "3 0 1 5");
+        let error = parse(text.as_bytes()).unwrap_err();
+        assert_eq!((error.line, error.token.as_str()), (11, "element"));
+
+        let bytes = binary();
+        let error = parse(&bytes[..bytes.len() - 2]).unwrap_err();
+        assert_eq!(error.line, 11);
+        assert!(error.message.contains("face"));
+    }
+
+    #[test]
+    fn comments_are_only_header_statements() {
+        // A property named like a comment, given to each vertex.
+        let text: String = ascii()
+            .lines()
+            .enumerate()
+            .map(|(index, line)| match index {
+                9 => format!("{}\nproperty uchar commented\n", line),
+                13..=17 => format!("{} 1\n", line),
+                _ => format!("{}\n", line),
+            })
+            .collect();
+        let model = parse(text.as_bytes()).unwrap();
+        assert_eq!(model.mesh.triangles.len(), 3);
+
+        // A comment naming the end of the header does not end it.
+        let text = ascii().replace("comment a square", "comment end_header and a square");
+        assert_eq!(parse(text.as_bytes()).unwrap().mesh.triangles.len(), 3);
+    }
+
+    #[test]
+    fn zero_normals_are_rejected() {
+        let text = "ply\nformat ascii 1.0\nelement vertex 1\nproperty float x\n\
+                    property float y\nproperty float z\nproperty float nx\n\
+                    property float ny\nproperty float nz\nend_header\n0 0 0 0 0 0\n";
+        let error = parse(text.as_bytes()).unwrap_err();
+        assert_eq!(error.line, 3);
+        assert!(error.message.contains("zero normal"));
+    }
+}
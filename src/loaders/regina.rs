@@ -0,0 +1,216 @@
+use crate::loaders::parse::ParseError;
+use crate::math::manifold::triangulation::{Permutation, Tetrahedron, Triangulation};
+
+const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789+-";
+
+/// What happens to each face as the signature walks over the tetrahedra.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FaceAction {
+    Boundary,
+    /// Glued by the identity to the next tetrahedron not seen yet.
+    NewTetrahedron,
+    /// Glued to a tetrahedron already seen, described by the next join.
+    Join,
+}
+
+/// A Regina isomorphism signature read one base64 character at a time.
+struct Signature {
+    chars: Vec<char>,
+    next: usize,
+}
+
+impl Signature {
+    fn error<S: Into<String>>(&self, index: usize, message: S) -> ParseError {
+        ParseError {
+            line: 1,
+            column: index + 1,
+            token: self
+                .chars
+                .get(index)
+                .map_or(String::new(), |c| c.to_string()),
+            message: message.into(),
+        }
+    }
+
+    fn value(&mut self, what: &str) -> Result<usize, ParseError> {
+        let index = self.next;
+        let c = *self
+            .chars
+            .get(index)
+            .ok_or_else(|| self.error(index, format!("signature ended before {}", what)))?;
+        self.next += 1;
+        ALPHABET
+            .find(c)
+            .ok_or_else(|| self.error(index, format!("invalid character in {}", what)))
+    }
+
+    /// A number spread over `length` characters, least significant first.
+    fn number(&mut self, length: usize, what: &str) -> Result<usize, ParseError> {
+        let mut number = 0;
+        for place in 0..length {
+            number |= self.value(what)? << (6 * place);
+        }
+        Ok(number)
+    }
+}
+
+/// The permutation of four vertices with the given index when all 24 are
+/// listed in lexicographic order of their images.
+fn ordered_permutation(index: usize) -> Permutation {
+    let mut remaining = vec![0, 1, 2, 3];
+    let mut images = [0; 4];
+    let mut index = index;
+    for (position, image) in images.iter_mut().enumerate() {
+        let block = [6, 2, 1, 1][position];
+        *image = remaining.remove(index / block);
+        index %= block;
+    }
+    Permutation::new(images)
+}
+
+/// Build the triangulation described by a Regina isomorphism signature, such
+/// as `cPcbbbiht` for the figure eight knot complement. Only the gluings are
+/// stored in a signature, so every tetrahedron is given the same regular
+/// shape; assign real geometry afterwards.
+pub fn from_isosig(signature: &str) -> Result<Triangulation, ParseError> {
+    let mut reader = Signature {
+        chars: signature.trim().chars().collect(),
+        next: 0,
+    };
+    let mut triangulation = Triangulation::new();
+    let mut size = reader.value("the number of tetrahedra")?;
+    let mut length = 1;
+    if size == 63 {
+        length = reader.value("the length of the tetrahedron count")?;
+        size = reader.number(length, "the number of tetrahedra")?;
+    }
+    if size == 0 {
+        return Ok(triangulation);
+    }
+
+    // Face actions are packed three to a character, two bits each. Gluing
+    // to a tetrahedron uses up the face on both sides.
+    let mut actions = Vec::new();
+    let mut faces = 0;
+    while faces < 4 * size {
+        let index = reader.next;
+        let packed = reader.value("the face actions")?;
+        for slot in 0..3 {
+            let action = (packed >> (2 * slot)) & 3;
+            if faces == 4 * size {
+                if action != 0 {
+                    return Err(reader.error(index, "face action after every face was used"));
+                }
+                continue;
+            }
+            let action = match action {
+                0 => FaceAction::Boundary,
+                1 => FaceAction::NewTetrahedron,
+                2 => FaceAction::Join,
+                _ => return Err(reader.error(index, "invalid face action")),
+            };
+            faces += if action == FaceAction::Boundary { 1 } else { 2 };
+            if faces > 4 * size {
+                return Err(reader.error(index, "face actions use too many faces"));
+            }
+            actions.push((index, action));
+        }
+    }
+    let joins = actions
+        .iter()
+        .filter(|&&(_, action)| action == FaceAction::Join)
+        .count();
+    let mut destinations = Vec::with_capacity(joins);
+    for _ in 0..joins {
+        let index = reader.next;
+        destinations.push((index, reader.number(length, "a join destination")?));
+    }
+    let mut permutations = Vec::with_capacity(joins);
+    for _ in 0..joins {
+        let index = reader.next;
+        let permutation = reader.value("a join permutation")?;
+        if permutation >= 24 {
+            return Err(reader.error(index, "permutation index out of range"));
+        }
+        permutations.push(ordered_permutation(permutation));
+    }
+    if reader.next < reader.chars.len() {
+        return Err(reader.error(reader.next, "unexpected characters after the signature"));
+    }
+
+    let vertices = Tetrahedron::regular_vertices(0.5);
+    for _ in 0..size {
+        triangulation.add_tetrahedron(vertices);
+    }
+    let mut actions = actions.into_iter();
+    let mut join = 0;
+    let mut next_unused = 1;
+    for tetrahedron in 0..size {
+        for face in 0..4 {
+            if triangulation.tetrahedra[tetrahedron].gluings[face].is_some() {
+                continue;
+            }
+            match actions.next() {
+                Some((_, FaceAction::Boundary)) | None => {}
+                Some((index, FaceAction::NewTetrahedron)) => {
+                    if next_unused >= size {
+                        return Err(reader.error(index, "more tetrahedra used than declared"));
+                    }
+                    triangulation.glue(tetrahedron, face, next_unused, Permutation::identity());
+                    next_unused += 1;
+                }
+                Some((_, FaceAction::Join)) => {
+                    let (index, target) = destinations[join];
+                    let permutation = permutations[join];
+                    let target_face = permutation[face];
+                    if target >= next_unused {
+                        return Err(reader.error(index, "join to a tetrahedron not seen yet"));
+                    }
+                    if (target == tetrahedron && target_face == face)
+                        || triangulation.tetrahedra[target].gluings[target_face].is_some()
+                    {
+                        return Err(reader.error(index, "join onto a face which is already glued"));
+                    }
+                    triangulation.glue(tetrahedron, face, target, permutation);
+                    join += 1;
+                }
+            }
+        }
+    }
+    Ok(triangulation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::complex::Complex;
+    use crate::math::manifold::cusp::CuspNeighborhoods;
+    use crate::math::manifold::ideal::IdealTriangulation;
+
+    #[test]
+    fn figure_eight_knot_complement() {
+        let combinatorics = from_isosig("cPcbbbiht").unwrap();
+        assert_eq!(combinatorics.size(), 2);
+        for tetrahedron in &combinatorics.tetrahedra {
+            assert!(tetrahedron.gluings.iter().all(Option::is_some));
+        }
+        // Two regular ideal tetrahedra make the complete structure.
+        let regular = Complex::from_polar(1.0, std::f32::consts::FRAC_PI_3);
+        let ideal = IdealTriangulation::new(&combinatorics, vec![regular; 2]);
+        assert!(ideal.satisfies_gluing_equations(1e-4));
+        assert_eq!(CuspNeighborhoods::maximal(&ideal.triangulation).cusps, 1);
+    }
+
+    #[test]
+    fn errors_point_at_the_bad_character() {
+        let error = from_isosig("cPcbb!iht").unwrap_err();
+        assert_eq!((error.line, error.column), (1, 6));
+        assert_eq!(error.token, "!");
+
+        let error = from_isosig("cPcbbbih").unwrap_err();
+        assert_eq!(error.column, 9);
+
+        let error = from_isosig("cPcbbbihtt").unwrap_err();
+        assert_eq!(error.column, 10);
+    }
+}
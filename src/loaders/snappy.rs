@@ -0,0 +1,249 @@
+use std::path::Path;
+
+use crate::loaders::parse::{LoadError, ParseError, Tokens};
+use crate::math::complex::Complex;
+use crate::math::manifold::triangulation::{Permutation, Tetrahedron, Triangulation};
+
+/// A triangulation read from a SnapPea / SnapPy `.tri` file.
+#[derive(Clone, Debug)]
+pub struct SnapPyTriangulation {
+    pub name: String,
+    pub orientable: bool,
+    pub triangulation: Triangulation,
+    /// The shape of each tetrahedron, if the file came with a solution to
+    /// the gluing equations.
+    pub shapes: Vec<Complex>,
+}
+
+const SOLUTION_TYPES: [&str; 8] = [
+    "not_attempted",
+    "geometric_solution",
+    "nongeometric_solution",
+    "flat_solution",
+    "degenerate_solution",
+    "other_solution",
+    "no_solution",
+    "externally_computed",
+];
+
+pub fn load(path: &Path) -> Result<SnapPyTriangulation, LoadError> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(parse(&text)?)
+}
+
+/// Parse the text of a `.tri` file. Only the tetrahedra, their gluings and
+/// their shapes are kept; cusp data and peripheral curves are checked and
+/// then skipped. Tetrahedra are given a regular shape until real geometry is
+/// assigned.
+pub fn parse(text: &str) -> Result<SnapPyTriangulation, ParseError> {
+    let mut tokens = Tokens::new(text, Some("%"));
+    let name = tokens.expect("the manifold name")?.text.to_string();
+
+    let solution = tokens.expect("a solution type")?;
+    if !SOLUTION_TYPES.contains(&solution.text) {
+        return Err(solution.error("unknown solution type"));
+    }
+    if let Some(volume) = tokens.on_line(&solution) {
+        volume.parse::<f32>("a volume")?;
+    }
+    let has_shapes = solution.text != "not_attempted";
+
+    let orientability = tokens.expect("the orientability")?;
+    // Left for the gluings to decide when the file does not say.
+    let stated = match orientability.text {
+        "oriented_manifold" => Some(true),
+        "nonorientable_manifold" => Some(false),
+        "unknown_orientability" => None,
+        _ => return Err(orientability.error("unknown orientability")),
+    };
+
+    let chern_simons = tokens.expect("the Chern-Simons invariant")?;
+    match chern_simons.text {
+        "CS_known" => {
+            tokens.parse::<f64>("the Chern-Simons invariant")?;
+        }
+        "CS_unknown" => {}
+        _ => return Err(chern_simons.error("expected CS_known or CS_unknown")),
+    }
+
+    let orientable_cusps: usize = tokens.parse("the number of orientable cusps")?;
+    let nonorientable_cusps: usize = tokens.parse("the number of nonorientable cusps")?;
+    let num_cusps = orientable_cusps + nonorientable_cusps;
+    for _ in 0..num_cusps {
+        let topology = tokens.expect("a cusp topology")?;
+        if topology.text != "torus" && topology.text != "Klein" {
+            return Err(topology.error("expected torus or Klein"));
+        }
+        tokens.parse::<f32>("a meridian filling")?;
+        tokens.parse::<f32>("a longitude filling")?;
+    }
+
+    let size: usize = tokens.parse("the number of tetrahedra")?;
+    let mut neighbours = Vec::with_capacity(size);
+    let mut shapes = Vec::new();
+    for _ in 0..size {
+        let mut tetrahedron = [(0, Permutation::identity()); 4];
+        let mut locations = [None; 4];
+        for (neighbour, _) in &mut tetrahedron {
+            let token = tokens.expect("a neighbouring tetrahedron")?;
+            *neighbour = token.parse("a neighbouring tetrahedron")?;
+            if *neighbour >= size {
+                return Err(token.error("no tetrahedron with this index"));
+            }
+        }
+        for (face, (_, permutation)) in tetrahedron.iter_mut().enumerate() {
+            let token = tokens.expect("a gluing permutation")?;
+            let mut images = [0; 4];
+            let mut seen = [false; 4];
+            if token.text.len() != 4 {
+                return Err(token.error("expected four digit permutation"));
+            }
+            for (image, digit) in images.iter_mut().zip(token.text.chars()) {
+                *image = digit.to_digit(4).map_or(4, |digit| digit as usize);
+                if *image == 4 || seen[*image] {
+                    return Err(token.error("expected a permutation of 0123"));
+                }
+                seen[*image] = true;
+            }
+            *permutation = Permutation::new(images);
+            locations[face] = Some(token);
+        }
+        for _ in 0..4 {
+            let token = tokens.expect("a cusp index")?;
+            let cusp: i64 = token.parse("a cusp index")?;
+            // -1 marks a finite vertex, which is in no cusp.
+            if cusp < -1 || cusp >= num_cusps as i64 {
+                return Err(token.error("no cusp with this index"));
+            }
+        }
+        // Meridian and longitude, each on both sheets of the cusp cover.
+        for _ in 0..64 {
+            tokens.parse::<i32>("a peripheral curve intersection number")?;
+        }
+        if has_shapes {
+            let real = tokens.parse("the real part of a shape")?;
+            let imaginary = tokens.parse("the imaginary part of a shape")?;
+            shapes.push(Complex::new(real, imaginary));
+        }
+        neighbours.push((tetrahedron, locations));
+    }
+    if let Some(token) = tokens.peek() {
+        return Err(token.error("unexpected data after the last tetrahedron"));
+    }
+
+    let mut triangulation = Triangulation::new();
+    let vertices = Tetrahedron::regular_vertices(0.5);
+    for _ in 0..size {
+        triangulation.add_tetrahedron(vertices);
+    }
+    for (tetrahedron, (gluings, locations)) in neighbours.iter().enumerate() {
+        for (face, &(target, permutation)) in gluings.iter().enumerate() {
+            let target_face = permutation[face];
+            if (target, target_face) < (tetrahedron, face) {
+                continue;
+            }
+            if (target, target_face) == (tetrahedron, face) {
+                let token = locations[face].expect("Every face has a permutation!");
+                return Err(token.error("face is glued to itself"));
+            }
+            triangulation.glue(tetrahedron, face, target, permutation);
+        }
+    }
+    let orientable = stated.unwrap_or_else(|| triangulation.is_orientable());
+    Ok(SnapPyTriangulation {
+        name,
+        orientable,
+        triangulation,
+        shapes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIGURE_EIGHT: &str = "% Triangulation
+m004
+geometric_solution  2.02988321
+oriented_manifold
+CS_known 0.0000000000000000
+
+1 0
+    torus   0.000000000000   0.000000000000
+
+2
+   1    1    1    1
+ 0132 1230 2310 2103
+   0    0    0    0
+ 0  0  0  0  0  0  1 -1  0  0  0  0  0  0  0  0
+ 0  0  0  0  0  0  0  0  0  0  0  0  0  0  0  0
+ 0  0  0  0  0  0  1 -1  0  0  0  0  0  0  0  0
+ 0  0  0  0  0  0  0  0  0  0  0  0  0  0  0  0
+  0.500000000000   0.866025403784
+
+   0    0    0    0
+ 0132 3201 3012 2031
+   0    0    0    0
+ 0  0  0  0  0  0  0  0  0  0  0  0  0  0  0  0
+ 0  0  0  0  0  0  0  0  0  0  0  0  0  0  0  0
+ 0  0  0  0  0  0  0  0  0  0  0  0  0  0  0  0
+ 0  0  0  0  0  0  0  0  0  0  0  0  0  0  0  0
+  0.500000000000   0.866025403784
+";
+
+    #[test]
+    fn figure_eight_knot_complement() {
+        let manifold = parse(FIGURE_EIGHT).unwrap();
+        assert_eq!(manifold.name, "m004");
+        assert!(manifold.orientable);
+        assert_eq!(manifold.triangulation.size(), 2);
+        for tetrahedron in &manifold.triangulation.tetrahedra {
+            assert!(tetrahedron.gluings.iter().all(Option::is_some));
+        }
+        assert_eq!(manifold.shapes.len(), 2);
+        for shape in &manifold.shapes {
+            assert!((shape.re - 0.5).abs() < 1e-6 && (shape.im - 0.866_025_4).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn unknown_orientability_comes_from_the_gluings() {
+        let text = FIGURE_EIGHT.replace("oriented_manifold", "unknown_orientability");
+        assert!(parse(&text).unwrap().orientable);
+
+        // Gluing one pair of faces by an even permutation instead makes the
+        // same tetrahedra a non-orientable manifold.
+        let text = text
+            .replace("0132 1230", "0123 1230")
+            .replace("0132 3201", "0123 3201");
+        assert!(!parse(&text).unwrap().orientable);
+
+        let text = FIGURE_EIGHT.replacen("   0    0    0    0\n", "  -1    0    0    0\n", 1);
+        assert!(parse(&text).is_ok());
+    }
+
+    #[test]
+    fn errors_point_at_the_bad_token() {
+        let text = FIGURE_EIGHT.replace("3201", "3x01");
+        let error = parse(&text).unwrap_err();
+        assert_eq!((error.line, error.column), (21, 7));
+        assert_eq!(error.token, "3x01");
+
+        let text = FIGURE_EIGHT.replace("oriented_manifold", "sideways_manifold");
+        assert_eq!(parse(&text).unwrap_err().line, 4);
+
+        let text = FIGURE_EIGHT.replace("   1    1    1    1", "   1    2    1    1");
+        let error = parse(&text).unwrap_err();
+        assert_eq!((error.line, error.column), (11, 9));
+
+        let text = FIGURE_EIGHT.replacen("   0    0    0    0\n", "   0   -7    0    0\n", 1);
+        let error = parse(&text).unwrap_err();
+        assert_eq!((error.line, error.token.as_str()), (13, "-7"));
+
+        // Cut off before the last shape.
+        let text = &FIGURE_EIGHT[..FIGURE_EIGHT.rfind("  0.5").unwrap()];
+        let error = parse(text).unwrap_err();
+        assert_eq!(error.line, 26);
+        assert!(error.message.contains("end of the file"));
+    }
+}
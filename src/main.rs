@@ -6,6 +6,7 @@ extern crate structopt;
 #[macro_use]
 extern crate clap;
 
+pub mod loaders;
 pub mod math;
 pub mod rendering;
 
@@ -16,42 +17,90 @@ use rand::rngs::SmallRng;
 use rand::{thread_rng, Rng, SeedableRng};
 use structopt::StructOpt;
 
+use loaders::{obj, ply, regina, snappy};
 use math::colliders::Collider;
 use math::colors::Color;
+use math::complex::Complex;
+use math::geometry::cuboid::BoxGeometry;
+use math::geometry::horosphere::HorosphereGeometry;
 use math::geometry::rect::RectGeometry;
 use math::geometry::sphere::SphereGeometry;
+use math::geometry::tube::TubeGeometry;
+use math::manifold::complex::CellComplex;
+use math::manifold::cusp::CuspNeighborhoods;
+use math::manifold::development::Development;
+use math::manifold::dirichlet::DirichletDomain;
+use math::manifold::holonomy::Holonomy;
+use math::manifold::ideal::IdealTriangulation;
+use math::manifold::identified_box::IdentifiedBox;
+use math::manifold::loops::LoopSearch;
+use math::manifold::triangulation::{Tetrahedron, Triangulation};
+use math::matrix::Mat4;
 use math::quaternion::Quaternion;
-use math::ray::Ray;
+use math::ray::{Ray, RayHit};
+use math::space::Geometry;
+use math::thurston::{GeodesicMarcher, ThurstonGeometry};
 use math::vectors::Vec3;
 
 use rendering::bvh::BVHNode;
 use rendering::camera::Camera;
 use rendering::materials::Material;
-use rendering::scene::Scene;
+use rendering::overlay::SkeletonOverlay;
+use rendering::scene::{Chart, Scene};
 use rendering::textures::{SampleMode, Texture, TextureIndex};
 
 const MIN_TIME: f32 = 0.001;
 const MAX_TIME: f32 = std::f32::MAX;
 const MAX_ITERATIONS: u32 = 50;
 
-fn color<T: Rng>(mut ray: Ray, scene: &Scene, rng: &mut T, between: &Uniform<f32>) -> Color {
+fn color<T: Rng>(
+    mut ray: Ray,
+    mut chart: usize,
+    scene: &Scene,
+    rng: &mut T,
+    between: &Uniform<f32>,
+) -> Color {
     let mut confirmed_color = Color::zero();
     let mut color_absorbed = Color::new(1.0, 1.0, 1.0);
+    let mut holonomy = Holonomy::new();
     for _ in 0..MAX_ITERATIONS {
-        if let Some((hit, material)) = scene.cast(&ray, MIN_TIME, MAX_TIME) {
+        if let Some(traced) = scene.trace_from(&ray, chart, holonomy, MIN_TIME, MAX_TIME) {
             let mut attenuation = Color::zero();
-            confirmed_color +=
-                color_absorbed * material.emit(&hit, rng, between, &scene.texture_atlas);
-            if let Some(new_ray) = material.scatter(
-                &ray,
-                &hit,
+            confirmed_color += color_absorbed
+                * traced
+                    .material
+                    .emit(&traced.hit, rng, between, &scene.texture_atlas);
+            // Materials scatter as if space were flat, which it is in the
+            // frame at the hit. Directions and normals are moved into that
+            // frame and the scattered direction is moved back out.
+            let geometry = scene.geometry;
+            let location = traced.hit.location;
+            let local_ray = Ray {
+                direction: geometry.tangent_to_local(location, traced.ray.direction),
+                ..traced.ray
+            };
+            let local_hit = RayHit {
+                normal: geometry.normal_to_local(location, traced.hit.normal),
+                ..traced.hit
+            };
+            if let Some(new_ray) = traced.material.scatter(
+                &local_ray,
+                &local_hit,
                 &mut attenuation,
                 rng,
                 between,
                 &scene.texture_atlas,
             ) {
                 color_absorbed *= attenuation;
-                ray = new_ray.cast_at(ray.cast_time);
+                // Scattered rays start from the hit, so they live in the chart
+                // the hit was found in.
+                ray = Ray {
+                    direction: geometry.tangent_from_local(location, new_ray.direction),
+                    ..new_ray
+                }
+                .cast_at(ray.cast_time);
+                chart = traced.chart;
+                holonomy = local_hit.holonomy;
             } else {
                 return confirmed_color;
             }
@@ -290,65 +339,7 @@ fn random_scene() -> Scene {
 }
 
 fn create_box(extents: Vec3) -> Collider {
-    let mut faces = Vec::new();
-    faces.push(Collider::from(RectGeometry::new(
-        -extents.z * Vec3::forward(),
-        2.0 * extents.x,
-        2.0 * extents.y,
-    )));
-    faces.push(
-        Collider::from(RectGeometry::new(
-            -extents.z * Vec3::forward(),
-            2.0 * extents.x,
-            2.0 * extents.y,
-        ))
-        .rotate(Quaternion::axis_angle(Vec3::up(), std::f32::consts::PI)),
-    );
-    faces.push(
-        Collider::from(RectGeometry::new(
-            -extents.x * Vec3::forward(),
-            2.0 * extents.z,
-            2.0 * extents.y,
-        ))
-        .rotate(Quaternion::axis_angle(
-            Vec3::up(),
-            std::f32::consts::FRAC_PI_2,
-        )),
-    );
-    faces.push(
-        Collider::from(RectGeometry::new(
-            -extents.x * Vec3::forward(),
-            2.0 * extents.z,
-            2.0 * extents.y,
-        ))
-        .rotate(Quaternion::axis_angle(
-            Vec3::up(),
-            -std::f32::consts::FRAC_PI_2,
-        )),
-    );
-    faces.push(
-        Collider::from(RectGeometry::new(
-            -extents.y * Vec3::forward(),
-            2.0 * extents.x,
-            2.0 * extents.z,
-        ))
-        .rotate(Quaternion::axis_angle(
-            Vec3::right(),
-            std::f32::consts::FRAC_PI_2,
-        )),
-    );
-    faces.push(
-        Collider::from(RectGeometry::new(
-            -extents.y * Vec3::forward(),
-            2.0 * extents.x,
-            2.0 * extents.z,
-        ))
-        .rotate(Quaternion::axis_angle(
-            Vec3::right(),
-            -std::f32::consts::FRAC_PI_2,
-        )),
-    );
-    Collider::Union(faces)
+    BoxGeometry::new(Vec3::zero(), extents).into()
 }
 
 #[allow(dead_code)]
@@ -701,7 +692,7 @@ fn cornell_projective_space(aspect: f32, use_sphere: bool) -> (Scene, Camera) {
 
     let box_rect = RectGeometry::new(Vec3::zero(), 1.0, 2.0);
     let projective_sphere = SphereGeometry::new(Vec3::zero(), 2.0);
-    //let light_sphere = SphereGeometry::new(Vec3::zero(), 0.25);
+    //let light_sphere = SphereGeometry::new(Vec3::zero(), 0.15);
 
     if true {
         scene.put(
@@ -786,13 +777,505 @@ fn cornell_projective_space(aspect: f32, use_sphere: bool) -> (Scene, Camera) {
     )
 }
 
+fn flat_manifold<M: Into<CellComplex>>(aspect: f32, space: M) -> (Scene, Camera) {
+    let mut scene = Scene::new();
+    scene.set_manifold(space);
+    let camera_pos = Vec3::new(-0.2, 0.1, -0.8);
+    let scene_center = Vec3::new(0.3, -0.1, 0.6);
+
+    let red_tex = scene.add_texture(Texture::Constant(Color::new(0.65, 0.05, 0.05)));
+    let white_tex = scene.add_texture(Texture::Constant(Color::new(0.73, 0.73, 0.73)));
+    let green_tex = scene.add_texture(Texture::Constant(Color::new(0.12, 0.45, 0.15)));
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+    let checker_tex = scene.add_texture(Texture::CheckerSurface(red_tex, white_tex, 8));
+    // In non-orientable spaces the green ball turns blue when seen mirrored.
+    let blue_tex = scene.add_texture(Texture::Constant(Color::new(0.1, 0.2, 0.6)));
+    let handed_tex = scene.add_texture(Texture::Handedness(green_tex, blue_tex));
+
+    // Everything has to fit inside the box, since each object is only seen
+    // through the cell it was placed in.
+    scene.put(
+        SphereGeometry::new(Vec3::new(0.5, 0.5, 0.5), 0.2).into(),
+        Material::Emissive {
+            texture: light_tex,
+            amplify: 6.0,
+        },
+    );
+    scene.put(
+        SphereGeometry::new(Vec3::new(-0.4, -0.3, 0.3), 0.3).into(),
+        Material::Lambertian {
+            albedo: checker_tex,
+        },
+    );
+    scene.put(
+        SphereGeometry::new(Vec3::new(0.4, -0.5, -0.2), 0.25).into(),
+        Material::Lambertian { albedo: handed_tex },
+    );
+    scene.put(
+        SphereGeometry::new(Vec3::new(-0.5, 0.5, -0.4), 0.2).into(),
+        Material::Metal {
+            albedo: white_tex,
+            fuzziness: 0.1,
+        },
+    );
+
+    (
+        scene,
+        Camera::new(camera_pos, scene_center, Vec3::up(), 70.0, aspect, 0.0, 1.0),
+    )
+}
+
+/// Half-turn space again, but found as the Dirichlet domain of its group
+/// rather than built by hand. About the origin this is the same box.
+fn half_turn_dirichlet() -> DirichletDomain {
+    let screw = Mat4::translation(Vec3::new(0.0, 0.0, 2.0))
+        * Mat4::rotation(Quaternion::axis_angle(
+            Vec3::forward(),
+            std::f32::consts::PI,
+        ));
+    let generators = [
+        Mat4::translation(Vec3::new(2.0, 0.0, 0.0)),
+        Mat4::translation(Vec3::new(0.0, 2.0, 0.0)),
+        screw,
+    ];
+    DirichletDomain::new(Geometry::Euclidean, Vec3::zero(), &generators, 3)
+}
+
+/// A single ball in the 3-torus, colored by which copy of the box it is seen
+/// in, to show the universal cover tiling space.
+fn torus_copies(aspect: f32) -> (Scene, Camera) {
+    let mut scene = Scene::new();
+    scene.set_manifold(IdentifiedBox::three_torus(Vec3::all(1.0)));
+    let palette = [
+        Color::new(0.73, 0.73, 0.73),
+        Color::new(0.65, 0.05, 0.05),
+        Color::new(0.12, 0.45, 0.15),
+        Color::new(0.1, 0.2, 0.6),
+        Color::new(0.8, 0.6, 0.2),
+        Color::new(0.5, 0.1, 0.6),
+        Color::new(0.1, 0.6, 0.6),
+    ];
+    let copies_tex = scene.add_texture(Texture::Copies(
+        palette
+            .iter()
+            .map(|&color| TextureIndex::Constant(color))
+            .collect(),
+    ));
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+    scene.put(
+        SphereGeometry::new(Vec3::zero(), 0.15).into(),
+        Material::Lambertian { albedo: copies_tex },
+    );
+    scene.put(
+        SphereGeometry::new(Vec3::new(0.4, 0.4, 0.4), 0.1).into(),
+        Material::Emissive {
+            texture: light_tex,
+            amplify: 8.0,
+        },
+    );
+    (
+        scene,
+        Camera::new(
+            Vec3::new(-0.3, 0.2, -0.4),
+            Vec3::new(0.5, 0.2, 1.0),
+            Vec3::up(),
+            70.0,
+            aspect,
+            0.0,
+            1.0,
+        ),
+    )
+}
+
+fn hyperbolic_space(aspect: f32) -> (Scene, Camera) {
+    let mut scene = Scene::new();
+    scene.geometry = Geometry::Hyperbolic;
+    let camera_pos = Vec3::new(0.0, 0.25, -0.45);
+    let scene_center = Vec3::new(0.3, 0.0, 0.6);
+
+    let red_tex = scene.add_texture(Texture::Constant(Color::new(0.65, 0.05, 0.05)));
+    let white_tex = scene.add_texture(Texture::Constant(Color::new(0.73, 0.73, 0.73)));
+    let green_tex = scene.add_texture(Texture::Constant(Color::new(0.12, 0.45, 0.15)));
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+    let sky_tex = scene.add_texture(Texture::Constant(Color::new(0.5, 0.7, 1.0)));
+    let checker_tex = scene.add_texture(Texture::CheckerSurface(red_tex, white_tex, 8));
+
+    // Spheres placed a fixed hyperbolic distance along each direction of a
+    // cube around the origin. Points of the Klein model a distance `d` from
+    // the origin sit at a chart distance of `tanh(d)`.
+    let spacing = 1.2f32;
+    for i in -1i32..=1 {
+        for j in -1..=1 {
+            for k in -1..=1 {
+                if i == 0 && j == 0 && k == 0 {
+                    continue;
+                }
+                let direction = Vec3::new(i as f32, j as f32, k as f32);
+                let center = (spacing * direction.length()).tanh() * direction.normalized();
+                let material = match (i + j + k).rem_euclid(3) {
+                    0 => Material::Lambertian {
+                        albedo: checker_tex,
+                    },
+                    1 => Material::Metal {
+                        albedo: white_tex,
+                        fuzziness: 0.05,
+                    },
+                    _ => Material::Lambertian { albedo: green_tex },
+                };
+                scene.put(SphereGeometry::new(center, 0.35).into(), material);
+            }
+        }
+    }
+    scene.put(
+        SphereGeometry::new(Vec3::zero(), 0.15).into(),
+        Material::lamp(light_tex, 7.0, 0.15, Geometry::Hyperbolic),
+    );
+    // A dim shell far away stands in for the sky.
+    scene.put(
+        SphereGeometry::new(Vec3::zero(), 6.0).into(),
+        Material::Emissive {
+            texture: sky_tex,
+            amplify: 0.5,
+        },
+    );
+
+    (
+        scene,
+        Camera::new(camera_pos, scene_center, Vec3::up(), 80.0, aspect, 0.0, 1.0)
+            .in_geometry(Geometry::Hyperbolic),
+    )
+}
+
+/// Make sure a triangulation really is a manifold before rendering it, since
+/// broken gluings otherwise just show up as black pixels.
+fn validated(triangulation: Triangulation) -> Triangulation {
+    if let Err(errors) = triangulation.validate() {
+        for error in &errors {
+            println!("Invalid triangulation: {}", error);
+        }
+        panic!("Cannot render an invalid triangulation!");
+    }
+    triangulation
+}
+
+fn spherical_space(aspect: f32) -> (Scene, Camera) {
+    let mut scene = Scene::new();
+    scene.set_manifold(validated(Triangulation::three_sphere()));
+    let camera_pos = Vec3::new(0.0, 0.3, -1.0);
+    let scene_center = Vec3::new(0.5, 0.0, 0.0);
+
+    let red_tex = scene.add_texture(Texture::Constant(Color::new(0.65, 0.05, 0.05)));
+    let white_tex = scene.add_texture(Texture::Constant(Color::new(0.73, 0.73, 0.73)));
+    let green_tex = scene.add_texture(Texture::Constant(Color::new(0.12, 0.45, 0.15)));
+    let blue_tex = scene.add_texture(Texture::Constant(Color::new(0.1, 0.2, 0.6)));
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+    let checker_tex = scene.add_texture(Texture::CheckerSurface(red_tex, white_tex, 8));
+
+    // One sphere at the center of each of the five tetrahedra. Radii are
+    // angles, and the whole 3-sphere is only 2 pi around.
+    scene.put_in(
+        0,
+        SphereGeometry::new(Vec3::zero(), 0.2).into(),
+        Material::Emissive {
+            texture: light_tex,
+            amplify: 8.0,
+        },
+    );
+    scene.put_in(
+        1,
+        SphereGeometry::new(Vec3::zero(), 0.35).into(),
+        Material::Lambertian {
+            albedo: checker_tex,
+        },
+    );
+    scene.put_in(
+        2,
+        SphereGeometry::new(Vec3::zero(), 0.35).into(),
+        Material::Metal {
+            albedo: white_tex,
+            fuzziness: 0.05,
+        },
+    );
+    scene.put_in(
+        3,
+        SphereGeometry::new(Vec3::zero(), 0.35).into(),
+        Material::Lambertian { albedo: green_tex },
+    );
+    scene.put_in(
+        4,
+        SphereGeometry::new(Vec3::zero(), 0.35).into(),
+        Material::Lambertian { albedo: blue_tex },
+    );
+
+    (
+        scene,
+        Camera::new(camera_pos, scene_center, Vec3::up(), 80.0, aspect, 0.0, 1.0)
+            .in_geometry(Geometry::Spherical),
+    )
+}
+
+/// The lens space L(p, q), with a ball in every tetrahedron colored by which
+/// copy of the fundamental domain it is seen in. Looking along the second
+/// circle of the lens space shows the same balls again every p-th of the way
+/// around, turned a further 2 pi q / p each time.
+fn lens_space(aspect: f32, p: u32, q: u32) -> (Scene, Camera) {
+    let triangulation = validated(Triangulation::lens_space(p, q));
+    let mut scene = Scene::new();
+    let palette = [
+        Color::new(0.73, 0.73, 0.73),
+        Color::new(0.65, 0.05, 0.05),
+        Color::new(0.12, 0.45, 0.15),
+        Color::new(0.1, 0.2, 0.6),
+        Color::new(0.8, 0.6, 0.2),
+        Color::new(0.5, 0.1, 0.6),
+        Color::new(0.1, 0.6, 0.6),
+    ];
+    let copies_tex = scene.add_texture(Texture::Copies(
+        palette
+            .iter()
+            .map(|&color| TextureIndex::Constant(color))
+            .collect(),
+    ));
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+
+    // Every tetrahedron is the same shape, centered on its chart's origin.
+    let tetrahedron = &triangulation.tetrahedra[0];
+    let inradius = (0..4)
+        .map(|face| Geometry::Spherical.plane_distance(Vec3::zero(), &tetrahedron.face_plane(face)))
+        .fold(f32::MAX, f32::min);
+    let along = (tetrahedron.vertices[3] - tetrahedron.vertices[2]).normalized();
+    let size = triangulation.size();
+    scene.set_manifold(triangulation);
+    for cell in 0..size {
+        let (radius, material) = if cell == 0 {
+            let radius = 0.2 * inradius;
+            (
+                radius,
+                Material::lamp(light_tex, 2.0, radius, Geometry::Spherical),
+            )
+        } else {
+            (0.35 * inradius, Material::Lambertian { albedo: copies_tex })
+        };
+        scene.put_in(
+            cell,
+            SphereGeometry::new(Vec3::zero(), radius).into(),
+            material,
+        );
+    }
+
+    let camera_pos = -0.6 * along;
+    (
+        scene,
+        Camera::new(camera_pos, along, Vec3::up(), 80.0, aspect, 0.0, 1.0)
+            .in_geometry(Geometry::Spherical),
+    )
+}
+
+/// The Poincare homology sphere, seen from inside its dodecahedron. Every
+/// face looks into a copy of the dodecahedron turned by a tenth of a turn.
+fn poincare_sphere(aspect: f32) -> (Scene, Camera) {
+    let mut scene = Scene::new();
+    scene.set_manifold(DirichletDomain::poincare_sphere());
+
+    let red_tex = scene.add_texture(Texture::Constant(Color::new(0.65, 0.05, 0.05)));
+    let white_tex = scene.add_texture(Texture::Constant(Color::new(0.73, 0.73, 0.73)));
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+    let checker_tex = scene.add_texture(Texture::CheckerSurface(red_tex, white_tex, 8));
+
+    scene.put(
+        SphereGeometry::new(Vec3::zero(), 0.12).into(),
+        Material::Lambertian {
+            albedo: checker_tex,
+        },
+    );
+    scene.put(
+        SphereGeometry::new(Vec3::new(0.15, 0.15, 0.1), 0.04).into(),
+        Material::lamp(light_tex, 2.0, 0.04, Geometry::Spherical),
+    );
+
+    let camera_pos = Vec3::new(0.05, 0.05, -0.25);
+    (
+        scene,
+        Camera::new(camera_pos, Vec3::zero(), Vec3::up(), 80.0, aspect, 0.0, 1.0)
+            .in_geometry(Geometry::Spherical),
+    )
+}
+
+/// A lattice of balls in one of the Thurston geometries, with every third
+/// one lit. Rays bend on their way through, so the lattice looks warped.
+fn thurston_space(aspect: f32, geometry: ThurstonGeometry) -> (Scene, Camera) {
+    let mut scene = Scene::new();
+    scene.marcher = Some(GeodesicMarcher {
+        max_distance: 10.0,
+        ..GeodesicMarcher::new(geometry)
+    });
+
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+    let red_tex = scene.add_texture(Texture::Constant(Color::new(0.65, 0.05, 0.05)));
+    let white_tex = scene.add_texture(Texture::Constant(Color::new(0.73, 0.73, 0.73)));
+    let checker_tex = scene.add_texture(Texture::CheckerSurface(red_tex, white_tex, 8));
+
+    for i in -1i32..=1 {
+        for j in -1i32..=1 {
+            for k in 0i32..=2 {
+                let center = Vec3::new(i as f32, j as f32, k as f32 + 0.5);
+                let material = if (i + j + k).rem_euclid(3) == 0 {
+                    Material::Emissive {
+                        texture: light_tex,
+                        amplify: 3.0,
+                    }
+                } else {
+                    let tint = Color::new(
+                        0.5 + 0.4 * i as f32,
+                        0.5 + 0.4 * j as f32,
+                        0.3 + 0.3 * k as f32,
+                    );
+                    Material::Lambertian {
+                        albedo: TextureIndex::Constant(tint),
+                    }
+                };
+                scene.put(SphereGeometry::new(center, 0.2).into(), material);
+            }
+        }
+    }
+    scene.put(
+        RectGeometry::new(Vec3::new(0.0, 0.0, 4.0), 8.0, 8.0).into(),
+        Material::Lambertian {
+            albedo: checker_tex,
+        },
+    );
+
+    (
+        scene,
+        Camera::new(
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::up(),
+            70.0,
+            aspect,
+            0.0,
+            1.0,
+        ),
+    )
+}
+
+/// Render inside a cusped hyperbolic manifold: the ideal triangulation loaded
+/// from `path`, or without a file the figure eight knot complement, built
+/// from two regular ideal tetrahedra. With a `cusp_depth` the maximal cusp
+/// neighbourhoods, shrunk by that distance, are drawn as well, checkered so
+/// that the flat tori bounding them show.
+fn cusped_manifold(
+    aspect: f32,
+    path: Option<&std::path::Path>,
+    cusp_depth: Option<f32>,
+) -> (Scene, Camera) {
+    let ideal = if let Some(path) = path {
+        let file = snappy::load(path)
+            .unwrap_or_else(|error| panic!("Could not load {}: {}", path.display(), error));
+        assert!(
+            !file.shapes.is_empty(),
+            "{} has no shapes to build the tetrahedra from!",
+            path.display()
+        );
+        IdealTriangulation::new(&file.triangulation, file.shapes)
+    } else {
+        let regular = Complex::from_polar(1.0, std::f32::consts::FRAC_PI_3);
+        let figure_eight = regina::from_isosig("cPcbbbiht").expect("Bad built in signature!");
+        IdealTriangulation::new(&figure_eight, vec![regular; 2])
+    };
+    if !ideal.satisfies_gluing_equations(1e-3) {
+        println!(
+            "Warning: shapes do not satisfy the gluing equations: {:?}",
+            ideal.gluing_equation_errors()
+        );
+    }
+    let triangulation = validated(ideal.triangulation);
+
+    let mut scene = Scene::new();
+    scene.set_manifold(&triangulation);
+    let red_tex = scene.add_texture(Texture::Constant(Color::new(0.65, 0.05, 0.05)));
+    let white_tex = scene.add_texture(Texture::Constant(Color::new(0.73, 0.73, 0.73)));
+    let light_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.9, 0.7)));
+    let checker_tex = scene.add_texture(Texture::CheckerSurface(red_tex, white_tex, 8));
+
+    // A sphere in the middle of every tetrahedron, small enough to stay
+    // inside it. Every other one is a light.
+    for (index, tetrahedron) in triangulation.tetrahedra.iter().enumerate() {
+        let center = tetrahedron.centroid();
+        let inradius = (0..4)
+            .map(|face| Geometry::Hyperbolic.plane_distance(center, &tetrahedron.face_plane(face)))
+            .fold(f32::MAX, f32::min);
+        let material = if index % 2 == 1 {
+            Material::Emissive {
+                texture: light_tex,
+                amplify: 6.0,
+            }
+        } else {
+            Material::Lambertian {
+                albedo: checker_tex,
+            }
+        };
+        scene.put_in(
+            index,
+            SphereGeometry::new(center, 0.3 * inradius).into(),
+            material,
+        );
+    }
+
+    if let Some(depth) = cusp_depth {
+        let blue_tex = scene.add_texture(Texture::Constant(Color::new(0.1, 0.2, 0.6)));
+        let horosphere_tex = scene.add_texture(Texture::CheckerSurface(blue_tex, white_tex, 4));
+        let cusps = CuspNeighborhoods::maximal(&triangulation).shrink(depth);
+        for tetrahedron in 0..triangulation.size() {
+            for (_, horosphere) in cusps.horospheres(tetrahedron) {
+                scene.put_in(
+                    tetrahedron,
+                    horosphere.into(),
+                    Material::Lambertian {
+                        albedo: horosphere_tex,
+                    },
+                );
+            }
+        }
+    }
+
+    // Start between the first sphere and one of the faces of its tetrahedron.
+    let first = &triangulation.tetrahedra[0];
+    let center = first.centroid();
+    let [a, b, c] = Tetrahedron::face_vertices(3);
+    let face_center = (first.vertices[a] + first.vertices[b] + first.vertices[c]) / 3.0;
+    let camera_pos = center + 0.5 * (face_center - center);
+
+    (
+        scene,
+        Camera::new(camera_pos, center, Vec3::up(), 80.0, aspect, 0.0, 1.0)
+            .in_geometry(Geometry::Hyperbolic),
+    )
+}
+
 arg_enum! {
     #[derive(Debug)]
     enum ChoosenScene {
         MaterialTest,
         Cornell,
         CornellHaze,
-        CornellProjectiveSpace
+        CornellProjectiveSpace,
+        ThreeTorus,
+        HalfTurnSpace,
+        HalfTurnDirichlet,
+        KleinBottleCrossCircle,
+        Kaleidoscope,
+        TorusCopies,
+        HyperbolicSpace,
+        SphericalSpace,
+        LensSpace,
+        PoincareSphere,
+        CuspedManifold,
+        Nil,
+        Sol,
+        SL2R,
+        H2xR,
+        S2xR
     }
 }
 
@@ -811,6 +1294,63 @@ struct Options {
     scene: ChoosenScene,
     #[structopt(short, long)]
     update: Option<u32>,
+    /// A SnapPy .tri file to render with the CuspedManifold scene.
+    #[structopt(long, parse(from_os_str))]
+    triangulation: Option<std::path::PathBuf>,
+    /// Draw the maximal cusps of the CuspedManifold scene, shrunk by this
+    /// distance.
+    #[structopt(long)]
+    cusp_depth: Option<f32>,
+    /// The p of L(p, q) for the LensSpace scene.
+    #[structopt(long, default_value = "5")]
+    p: u32,
+    /// The q of L(p, q) for the LensSpace scene.
+    #[structopt(long, default_value = "2")]
+    q: u32,
+    /// Render this many frames, walking the camera between each one. Frames
+    /// are numbered onto the end of the output file's name.
+    #[structopt(long, default_value = "1")]
+    frames: u32,
+    /// How far the camera walks forward between frames.
+    #[structopt(long, default_value = "0.05")]
+    step: f32,
+    /// How many degrees the camera turns to the right between frames.
+    #[structopt(long, default_value = "0")]
+    turn: f32,
+    /// Report the injectivity radius a little in front of the camera, and
+    /// draw this many of the shortest geodesic loops through that point.
+    #[structopt(long, default_value = "0")]
+    loops: usize,
+    /// Outline the cells the manifold is built from, tinting each face by
+    /// the face it is glued to.
+    #[structopt(long)]
+    skeleton: bool,
+    /// Replace the manifold by this many layers of copies of its cells laid
+    /// out in the universal cover, around the camera's cell.
+    #[structopt(long)]
+    unroll: Option<usize>,
+    /// Also write the unrolled cells to this Wavefront OBJ file.
+    #[structopt(long, parse(from_os_str))]
+    unroll_obj: Option<std::path::PathBuf>,
+    /// A Wavefront .obj model to add to the scene, in the camera's chart.
+    #[structopt(long, parse(from_os_str))]
+    obj: Option<std::path::PathBuf>,
+    /// A .ply mesh to add to the scene, in the camera's chart, colored by
+    /// its vertex colors.
+    #[structopt(long, parse(from_os_str))]
+    ply: Option<std::path::PathBuf>,
+}
+
+fn frame_path(out_file: &std::path::Path, frame: u32) -> std::path::PathBuf {
+    let stem = out_file
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("frame");
+    let extension = out_file
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("png");
+    out_file.with_file_name(format!("{}_{:04}.{}", stem, frame, extension))
 }
 
 fn main() {
@@ -819,31 +1359,262 @@ fn main() {
     let aspect = options.width as f32 / options.height as f32;
     let delta_time = 1.0 / 30.0;
 
-    let mut tmp_image = RgbImage::new(options.width, options.height);
-
     let (mut scene, camera) = match options.scene {
         ChoosenScene::MaterialTest => test_textures_scene(aspect),
         ChoosenScene::Cornell => cornell_box(aspect),
         ChoosenScene::CornellHaze => cornell_box_with_haze(aspect),
         ChoosenScene::CornellProjectiveSpace => cornell_projective_space(aspect, true),
+        ChoosenScene::ThreeTorus => {
+            flat_manifold(aspect, IdentifiedBox::three_torus(Vec3::all(1.0)))
+        }
+        ChoosenScene::HalfTurnSpace => {
+            flat_manifold(aspect, IdentifiedBox::half_turn_space(Vec3::all(1.0)))
+        }
+        ChoosenScene::HalfTurnDirichlet => flat_manifold(aspect, half_turn_dirichlet()),
+        ChoosenScene::KleinBottleCrossCircle => flat_manifold(
+            aspect,
+            IdentifiedBox::klein_bottle_cross_circle(Vec3::all(1.0)),
+        ),
+        ChoosenScene::Kaleidoscope => {
+            flat_manifold(aspect, IdentifiedBox::kaleidoscope(Vec3::all(1.0)))
+        }
+        ChoosenScene::TorusCopies => torus_copies(aspect),
+        ChoosenScene::HyperbolicSpace => hyperbolic_space(aspect),
+        ChoosenScene::SphericalSpace => spherical_space(aspect),
+        ChoosenScene::LensSpace => lens_space(aspect, options.p, options.q),
+        ChoosenScene::PoincareSphere => poincare_sphere(aspect),
+        ChoosenScene::CuspedManifold => {
+            cusped_manifold(aspect, options.triangulation.as_deref(), options.cusp_depth)
+        }
+        ChoosenScene::Nil => thurston_space(aspect, ThurstonGeometry::Nil),
+        ChoosenScene::Sol => thurston_space(aspect, ThurstonGeometry::Sol),
+        ChoosenScene::SL2R => thurston_space(aspect, ThurstonGeometry::SL2R),
+        ChoosenScene::H2xR => thurston_space(aspect, ThurstonGeometry::H2xR),
+        ChoosenScene::S2xR => thurston_space(aspect, ThurstonGeometry::S2xR),
+    };
+    if let Some(ref path) = options.obj {
+        let model = obj::load(path)
+            .unwrap_or_else(|error| panic!("Could not load {}: {}", path.display(), error));
+        for (collider, material) in model.renderables(&mut scene.texture_atlas) {
+            scene.put_in(camera.chart, collider, material);
+        }
+    }
+    if let Some(ref path) = options.ply {
+        let model = ply::load(path)
+            .unwrap_or_else(|error| panic!("Could not load {}: {}", path.display(), error));
+        let (collider, material) = model.renderable(&mut scene.texture_atlas);
+        scene.put_in(camera.chart, collider, material);
+    }
+    if options.loops > 0 {
+        draw_loops(&mut scene, &camera, options.loops);
+    }
+    let camera = match options.unroll {
+        Some(layers) => unroll(&mut scene, camera, layers, options.unroll_obj.as_deref()),
+        None => camera,
     };
     scene.compute_hierarchy(0.0, delta_time);
-    if let &Some(ref hierarchy) = &scene.hierarchy {
-        let mut total_volume = 0.0;
-        let mut num_volumes = 0.0;
-        for node in &hierarchy.hierarchy_heap {
-            if let BVHNode::Split(geom) = node {
-                num_volumes += 1.0;
-                total_volume += geom.volume();
+    for chart in &scene.charts {
+        if let &Some(ref hierarchy) = &chart.hierarchy {
+            let mut total_volume = 0.0;
+            let mut num_volumes = 0.0;
+            for node in &hierarchy.hierarchy_heap {
+                if let BVHNode::Split(geom) = node {
+                    num_volumes += 1.0;
+                    total_volume += geom.volume();
+                }
             }
+            println!("Average Bounding Volume: {}", total_volume / num_volumes);
         }
-        println!("Average Bounding Volume: {}", total_volume / num_volumes);
     }
     println!(
         "Camera: ({:?}, {:?}, {:?})",
         camera.horizontal, camera.vertical, camera.forward
     );
 
+    let overlay = match scene.manifold {
+        Some(ref manifold) if options.skeleton => Some(SkeletonOverlay::new(manifold, 0.01)),
+        _ => None,
+    };
+
+    let mut camera = camera;
+    for frame in 0..options.frames {
+        let out_file = if options.frames > 1 {
+            frame_path(&options.out_file, frame)
+        } else {
+            options.out_file.clone()
+        };
+        render(
+            &scene,
+            overlay.as_ref(),
+            &camera,
+            &options,
+            &out_file,
+            delta_time,
+        );
+        camera = camera
+            .move_by(Vec3::forward() * options.step, scene.manifold.as_ref())
+            .rotate_by(Quaternion::axis_angle(
+                Vec3::up(),
+                options.turn * std::f32::consts::PI / 180.0,
+            ));
+    }
+}
+
+/// Find the geodesic loops through a point just in front of the camera,
+/// print their lengths and draw the shortest `count` of them as glowing
+/// tubes.
+fn draw_loops(scene: &mut Scene, camera: &Camera, count: usize) {
+    let manifold = match scene.manifold.clone() {
+        Some(manifold) => manifold,
+        None => {
+            println!("Geodesic loops need a scene inside a manifold.");
+            return;
+        }
+    };
+    let base = camera
+        .clone()
+        .move_by(Vec3::forward() * 0.25, Some(&manifold));
+    let loops = LoopSearch::new().find(&manifold, base.chart, base.location);
+    let shortest = match loops.first() {
+        Some(shortest) => shortest.length,
+        None => {
+            println!("No geodesic loops found.");
+            return;
+        }
+    };
+    println!(
+        "Injectivity radius at {} in cell {}: {}",
+        base.location,
+        base.chart,
+        0.5 * shortest
+    );
+    let glow_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 0.6, 0.2)));
+    for geodesic_loop in loops.iter().take(count) {
+        println!(
+            "Loop of length {} through {} gluings",
+            geodesic_loop.length,
+            geodesic_loop.holonomy.length()
+        );
+        let tubes = geodesic_loop.tubes(&manifold, base.chart, base.location, 0.01 * shortest);
+        for (cell, tube) in tubes {
+            scene.put_in(
+                cell,
+                tube.into(),
+                Material::Emissive {
+                    texture: glow_tex,
+                    amplify: 4.0,
+                },
+            );
+        }
+    }
+}
+
+/// Swap the manifold of the scene for its development around the camera's
+/// cell: copies of the spheres, horospheres and tubes in every copy of their
+/// cells, and glowing tubes along the edges of the cells. Rendered without
+/// the gluings, this should look just like the manifold did.
+fn unroll(
+    scene: &mut Scene,
+    camera: Camera,
+    layers: usize,
+    obj: Option<&std::path::Path>,
+) -> Camera {
+    let manifold = match scene.manifold.take() {
+        Some(manifold) => manifold,
+        None => {
+            println!("Only scenes inside a manifold can be unrolled.");
+            return camera;
+        }
+    };
+    let development = Development::new(&manifold, camera.chart, layers);
+    println!("Unrolled {} copies of the cells", development.copies.len());
+    if let Some(path) = obj {
+        let mut file = std::fs::File::create(path).expect("Failed to create OBJ file.");
+        development
+            .write_obj(&manifold, &mut file)
+            .expect("Failed to write OBJ file.");
+    }
+
+    let charts = std::mem::replace(&mut scene.charts, vec![Chart::new()]);
+    let mut skipped = 0;
+    for copy in &development.copies {
+        // Copies reached through an orientation reversing gluing are seen
+        // mirrored, as rays crossing that gluing would see them.
+        let reversed = copy.placement.determinant() < 0.0;
+        for renderable in &charts[copy.cell].renderables {
+            let collider = if development.geometry == Geometry::Euclidean {
+                // Flat placements are affine, so anything can ride along
+                // with them, and they flip handedness on their own.
+                renderable
+                    .collider
+                    .try_clone()
+                    .map(|collider| collider.transform(copy.placement))
+            } else {
+                match &renderable.collider {
+                    Collider::Sphere(sphere) => development
+                        .place(copy, sphere.center)
+                        .map(|center| SphereGeometry::new(center, sphere.radius).into()),
+                    Collider::Horosphere(horosphere) => {
+                        Some(HorosphereGeometry::from_pole(copy.placement * horosphere.pole).into())
+                    }
+                    Collider::Tube(tube) => {
+                        match (
+                            development.place(copy, tube.start),
+                            development.place(copy, tube.end),
+                        ) {
+                            (Some(start), Some(end)) => {
+                                Some(TubeGeometry::new(start, end, tube.radius).into())
+                            }
+                            _ => None,
+                        }
+                    }
+                    // Rects and the rest can only be moved by Euclidean
+                    // isometries.
+                    _ => None,
+                }
+                .map(|collider: Collider| {
+                    if reversed {
+                        collider.mirrored()
+                    } else {
+                        collider
+                    }
+                })
+            };
+            match collider {
+                Some(collider) => scene.put(collider, renderable.material),
+                None => skipped += 1,
+            }
+        }
+    }
+    if skipped > 0 {
+        println!(
+            "Warning: left out {} copies of colliders which cannot be moved into the development",
+            skipped
+        );
+    }
+    let edge_tex = scene.add_texture(Texture::Constant(Color::new(1.0, 1.0, 1.0)));
+    for (_, start, end) in development.edges(&manifold) {
+        scene.put(
+            TubeGeometry::new(start, end, 0.01).into(),
+            Material::Emissive {
+                texture: edge_tex,
+                amplify: 1.0,
+            },
+        );
+    }
+    camera.in_chart(0)
+}
+
+fn render(
+    scene: &Scene,
+    overlay: Option<&SkeletonOverlay>,
+    camera: &Camera,
+    options: &Options,
+    out_file: &std::path::Path,
+    delta_time: f32,
+) {
+    let mut tmp_image = RgbImage::new(options.width, options.height);
+
     let mut rng = thread_rng();
     let between = Uniform::new(0.0, 1.0);
 
@@ -877,7 +1648,11 @@ fn main() {
                 let ray = camera
                     .world_ray(u, v)
                     .cast_at(delta_time * rng.sample(between));
-                color_accumulator += color(ray, &scene, &mut rng, &between);
+                let sample = color(ray, camera.chart, scene, &mut rng, &between);
+                color_accumulator += match overlay {
+                    Some(overlay) => overlay.shade(scene, &ray, camera.chart, sample),
+                    None => sample,
+                };
                 progress_bar.inc(1);
             }
             color_accumulator /= options.samples as f32;
@@ -886,16 +1661,12 @@ fn main() {
         }
         if let Some(rows_to_update) = options.update {
             if x % rows_to_update == 0 {
-                tmp_image
-                    .save(options.out_file.clone())
-                    .expect("Failed to save image.");
+                tmp_image.save(out_file).expect("Failed to save image.");
             }
         }
     }
     progress_bar.finish_with_message("done!");
     println!("Time to render: {}", time.elapsed().as_millis());
 
-    tmp_image
-        .save(options.out_file)
-        .expect("Failed to save image.");
+    tmp_image.save(out_file).expect("Failed to save image.");
 }
@@ -1,18 +1,37 @@
 use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::geometry::cuboid::BoxGeometry;
+use crate::math::geometry::horosphere::HorosphereGeometry;
+use crate::math::geometry::mesh::MeshGeometry;
 use crate::math::geometry::rect::RectGeometry;
 use crate::math::geometry::sphere::SphereGeometry;
+use crate::math::geometry::triangle::TriangleGeometry;
+use crate::math::geometry::tube::TubeGeometry;
 use crate::math::geometry::volumes::ConstantVolume;
+use crate::math::matrix::Mat4;
 use crate::math::quaternion::Quaternion;
 use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::thurston::{GeodesicPiece, MarchHit, RayMarchable};
 use crate::math::vectors::Vec3;
 
 pub enum Collider {
     Sphere(SphereGeometry),
     SphereWithVelocity(SphereGeometry, Vec3),
     Rect(RectGeometry),
+    Cuboid(BoxGeometry),
+    Horosphere(HorosphereGeometry),
+    Tube(TubeGeometry),
+    Triangle(TriangleGeometry),
+    Mesh(MeshGeometry),
     Volume(ConstantVolume),
     Translate(Vec3, Box<Collider>),
     Rotate(Quaternion, Box<Collider>),
+    /// An affine map taking the collider's coordinates to the scene's,
+    /// followed by its inverse. Like translations and rotations these act
+    /// on chart coordinates, so they only keep their shape in flat space.
+    Transform(Mat4, Mat4, Box<Collider>),
+    /// The collider with the handedness of its hits flipped, as for a copy
+    /// seen through an orientation reversing gluing. Its shape is left alone.
+    Mirrored(Box<Collider>),
     Union(Vec<Collider>),
 }
 
@@ -28,6 +47,11 @@ impl RayCollidable for Collider {
                 Some(begin_aabb.unwrap() + end_aabb.unwrap())
             }
             &Rect(ref geometry) => geometry.bounding_box(t_min, t_max),
+            Cuboid(geometry) => geometry.bounding_box(t_min, t_max),
+            Horosphere(geometry) => geometry.bounding_box(t_min, t_max),
+            Tube(geometry) => geometry.bounding_box(t_min, t_max),
+            Triangle(geometry) => geometry.bounding_box(t_min, t_max),
+            Mesh(geometry) => geometry.bounding_box(t_min, t_max),
             &Volume(ref volume) => volume.bounding_box(t_min, t_max),
             &Translate(offset, ref collider) => {
                 collider.bounding_box(t_min, t_max).map(|x| x + offset)
@@ -35,6 +59,10 @@ impl RayCollidable for Collider {
             &Rotate(rotation, ref collider) => {
                 collider.bounding_box(t_min, t_max).map(|x| rotation * x)
             }
+            Transform(matrix, _, collider) => {
+                collider.bounding_box(t_min, t_max).map(|x| *matrix * x)
+            }
+            Mirrored(collider) => collider.bounding_box(t_min, t_max),
             &Union(ref colliders) => {
                 if colliders.len() == 0 {
                     None
@@ -63,12 +91,16 @@ impl RayCollidable for Collider {
                 .offset(velocity * ray.cast_time)
                 .hit(ray, t_min, t_max),
             &Rect(ref geometry) => geometry.hit(ray, t_min, t_max),
+            Cuboid(geometry) => geometry.hit(ray, t_min, t_max),
+            Horosphere(geometry) => geometry.hit(ray, t_min, t_max),
+            Tube(geometry) => geometry.hit(ray, t_min, t_max),
+            Triangle(geometry) => geometry.hit(ray, t_min, t_max),
+            Mesh(geometry) => geometry.hit(ray, t_min, t_max),
             &Volume(ref volume) => volume.hit(ray, t_min, t_max),
             &Translate(offset, ref collider) => {
                 let offset_ray = Ray {
-                    cast_time: ray.cast_time,
                     origin: ray.origin - offset,
-                    direction: ray.direction,
+                    ..*ray
                 };
                 if let Some(mut hit) = collider.hit(&offset_ray, t_min, t_max) {
                     hit.location += offset;
@@ -80,9 +112,9 @@ impl RayCollidable for Collider {
             &Rotate(rotation, ref collider) => {
                 let inv_rotation = rotation.inv();
                 let offset_ray = Ray {
-                    cast_time: ray.cast_time,
                     origin: inv_rotation * ray.origin,
                     direction: inv_rotation * ray.direction,
+                    ..*ray
                 };
                 if let Some(mut hit) = collider.hit(&offset_ray, t_min, t_max) {
                     hit.location = rotation * hit.location;
@@ -92,6 +124,33 @@ impl RayCollidable for Collider {
                     None
                 }
             }
+            Transform(matrix, inverse, collider) => {
+                // The local ray moves at a different speed, so its
+                // parameters are stretched to keep hits in step with the
+                // rest of the scene. A map which reflects flips the ray's
+                // handedness, as an orientation reversing gluing does.
+                let direction = inverse.transform_direction(ray.direction);
+                let stretch = direction.length();
+                let local_ray = Ray {
+                    origin: inverse.transform_point(ray.origin),
+                    direction: direction / stretch,
+                    mirrored: ray.mirrored != (matrix.determinant() < 0.0),
+                    ..*ray
+                };
+                let mut hit = collider.hit(&local_ray, t_min * stretch, t_max * stretch)?;
+                hit.hit_fraction /= stretch;
+                hit.location = matrix.transform_point(hit.location);
+                hit.normal = inverse
+                    .transpose()
+                    .transform_direction(hit.normal)
+                    .normalized();
+                Some(hit)
+            }
+            Mirrored(collider) => {
+                let mut hit = collider.hit(ray, t_min, t_max)?;
+                hit.mirrored = !hit.mirrored;
+                Some(hit)
+            }
             &Union(ref colliders) => {
                 let mut best_hit: Option<RayHit> = None;
                 let mut earliest_time = t_max;
@@ -108,6 +167,54 @@ impl RayCollidable for Collider {
     }
 }
 
+impl RayMarchable for Collider {
+    /// Volumes are not supported, and are never hit. Neither are horospheres,
+    /// which only exist in hyperbolic space, tubes, triangles or meshes.
+    fn march_hit(&self, piece: GeodesicPiece, length: f32, cast_time: f32) -> Option<MarchHit> {
+        use Collider::*;
+        match self {
+            Sphere(geometry) => geometry.march_hit(piece, length, cast_time),
+            SphereWithVelocity(geometry, velocity) => geometry
+                .offset(velocity * cast_time)
+                .march_hit(piece, length, cast_time),
+            Rect(geometry) => geometry.march_hit(piece, length, cast_time),
+            Cuboid(geometry) => geometry.march_hit(piece, length, cast_time),
+            Horosphere(_) | Tube(_) | Triangle(_) | Mesh(_) | Volume(_) => None,
+            Translate(offset, collider) => {
+                collider.march_hit(&|s| piece(s) - *offset, length, cast_time)
+            }
+            Rotate(rotation, collider) => {
+                let inv_rotation = rotation.inv();
+                let mut hit =
+                    collider.march_hit(&|s| inv_rotation * piece(s), length, cast_time)?;
+                hit.gradient = rotation * hit.gradient;
+                Some(hit)
+            }
+            Transform(_, inverse, collider) => {
+                let mut hit = collider.march_hit(
+                    &|s| inverse.transform_point(piece(s)),
+                    length,
+                    cast_time,
+                )?;
+                hit.gradient = inverse.transpose().transform_direction(hit.gradient);
+                Some(hit)
+            }
+            Mirrored(collider) => collider.march_hit(piece, length, cast_time),
+            Union(colliders) => {
+                let mut best_hit: Option<MarchHit> = None;
+                for collider in colliders {
+                    if let Some(hit) = collider.march_hit(piece, length, cast_time) {
+                        if best_hit.is_none_or(|best| hit.distance < best.distance) {
+                            best_hit = Some(hit);
+                        }
+                    }
+                }
+                best_hit
+            }
+        }
+    }
+}
+
 impl From<SphereGeometry> for Collider {
     fn from(geometry: SphereGeometry) -> Collider {
         Collider::Sphere(geometry)
@@ -120,6 +227,36 @@ impl From<RectGeometry> for Collider {
     }
 }
 
+impl From<BoxGeometry> for Collider {
+    fn from(geometry: BoxGeometry) -> Collider {
+        Collider::Cuboid(geometry)
+    }
+}
+
+impl From<HorosphereGeometry> for Collider {
+    fn from(geometry: HorosphereGeometry) -> Collider {
+        Collider::Horosphere(geometry)
+    }
+}
+
+impl From<TubeGeometry> for Collider {
+    fn from(geometry: TubeGeometry) -> Collider {
+        Collider::Tube(geometry)
+    }
+}
+
+impl From<TriangleGeometry> for Collider {
+    fn from(geometry: TriangleGeometry) -> Collider {
+        Collider::Triangle(geometry)
+    }
+}
+
+impl From<MeshGeometry> for Collider {
+    fn from(geometry: MeshGeometry) -> Collider {
+        Collider::Mesh(geometry)
+    }
+}
+
 impl Collider {
     pub fn with_velocity(self, velocity: Vec3) -> Collider {
         use Collider::*;
@@ -127,9 +264,16 @@ impl Collider {
             Sphere(geometry) => SphereWithVelocity(geometry, velocity),
             SphereWithVelocity(geometry, _) => SphereWithVelocity(geometry, velocity),
             Rect(geometry) => Rect(geometry),
+            Cuboid(geometry) => Cuboid(geometry),
+            Horosphere(geometry) => Horosphere(geometry),
+            Tube(geometry) => Tube(geometry),
+            Triangle(geometry) => Triangle(geometry),
+            Mesh(geometry) => Mesh(geometry),
             Volume(vol) => Volume(vol),
             Translate(offset, collider) => Translate(offset, collider),
             Rotate(rotation, collider) => Rotate(rotation, collider),
+            Transform(matrix, inverse, collider) => Transform(matrix, inverse, collider),
+            Mirrored(collider) => Mirrored(collider),
             Union(colliders) => Union(colliders),
         }
     }
@@ -142,6 +286,51 @@ impl Collider {
         Collider::Rotate(rotation, Box::new(self))
     }
 
+    /// Move the collider by an invertible affine map, such as a 3x4 matrix
+    /// from `Mat4::affine`.
+    pub fn transform(self, matrix: Mat4) -> Collider {
+        let inverse = matrix
+            .inverse()
+            .expect("Collider transforms must be invertible!");
+        Collider::Transform(matrix, inverse, Box::new(self))
+    }
+
+    pub fn scale(self, factors: Vec3) -> Collider {
+        self.transform(Mat4::scale(factors))
+    }
+
+    pub fn mirrored(self) -> Collider {
+        Collider::Mirrored(Box::new(self))
+    }
+
+    /// A copy of the collider, if it can be made. Volumes keep their
+    /// boundary behind a trait object, so they cannot be copied.
+    pub fn try_clone(&self) -> Option<Collider> {
+        use Collider::*;
+        let boxed = |collider: &Collider| collider.try_clone().map(Box::new);
+        Some(match self {
+            Sphere(geometry) => Sphere(*geometry),
+            SphereWithVelocity(geometry, velocity) => SphereWithVelocity(*geometry, *velocity),
+            Rect(geometry) => Rect(*geometry),
+            Cuboid(geometry) => Cuboid(*geometry),
+            Horosphere(geometry) => Horosphere(*geometry),
+            Tube(geometry) => Tube(*geometry),
+            Triangle(geometry) => Triangle(*geometry),
+            Mesh(geometry) => Mesh(geometry.clone()),
+            Volume(_) => return None,
+            Translate(offset, collider) => Translate(*offset, boxed(collider)?),
+            Rotate(rotation, collider) => Rotate(*rotation, boxed(collider)?),
+            Transform(matrix, inverse, collider) => Transform(*matrix, *inverse, boxed(collider)?),
+            Mirrored(collider) => Mirrored(boxed(collider)?),
+            Union(colliders) => Union(
+                colliders
+                    .iter()
+                    .map(Collider::try_clone)
+                    .collect::<Option<Vec<_>>>()?,
+            ),
+        })
+    }
+
     pub fn to_volume(self, density: f32) -> Collider {
         Collider::Volume(ConstantVolume {
             boundary: Box::new(self),
@@ -149,3 +338,47 @@ impl Collider {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-4
+    }
+
+    #[test]
+    fn stretched_normals_follow_the_surface() {
+        // The ellipsoid x^2 / 4 + y^2 + z^2 = 1.
+        let ellipsoid =
+            Collider::from(SphereGeometry::new(Vec3::zero(), 1.0)).scale(Vec3::new(2.0, 1.0, 1.0));
+        let depth = 0.5f32.sqrt();
+        let ray = Ray::new(Vec3::new(1.0, 0.5, -5.0), Vec3::forward());
+        let hit = ellipsoid.hit(&ray, 0.0, f32::MAX).unwrap();
+        assert!((hit.hit_fraction - (5.0 - depth)).abs() < 1e-4);
+        assert!(close(hit.location, Vec3::new(1.0, 0.5, -depth)));
+        // The gradient of the ellipsoid's equation, not the stretched
+        // normal of the sphere.
+        let gradient = Vec3::new(0.5, 1.0, -2.0 * depth).normalized();
+        assert!(close(hit.normal, gradient));
+    }
+
+    #[test]
+    fn transformed_bounds_hold_every_corner() {
+        let matrix = Mat4::affine([
+            [2.0, 1.0, 0.0, 3.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.5, 1.0, 0.0],
+        ]);
+        let sheared = Collider::from(SphereGeometry::new(Vec3::zero(), 1.0)).transform(matrix);
+        let bounds = sheared.bounding_box(0.0, 0.0).unwrap();
+        assert!(close(bounds.center, Vec3::new(3.0, 0.0, 0.0)));
+        assert!(close(bounds.extents, Vec3::new(3.0, 1.0, 1.5)));
+        for corner in 0..8 {
+            let sign = |bit: usize| if corner & (1 << bit) == 0 { -1.0 } else { 1.0 };
+            let point = matrix.transform_point(Vec3::new(sign(0), sign(1), sign(2)));
+            let offset = point - bounds.center;
+            assert!((0..3).all(|axis| offset[axis].abs() <= bounds.extents[axis] + 1e-4));
+        }
+    }
+}
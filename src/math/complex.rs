@@ -0,0 +1,144 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Complex {
+    pub re: f32,
+    pub im: f32,
+}
+
+impl Complex {
+    pub fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+    pub fn zero() -> Complex {
+        Complex::new(0.0, 0.0)
+    }
+    pub fn one() -> Complex {
+        Complex::new(1.0, 0.0)
+    }
+    pub fn i() -> Complex {
+        Complex::new(0.0, 1.0)
+    }
+    pub fn from_polar(modulus: f32, argument: f32) -> Complex {
+        Complex::new(modulus * argument.cos(), modulus * argument.sin())
+    }
+    pub fn conj(&self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+    pub fn norm_sq(&self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+    pub fn norm(&self) -> f32 {
+        self.norm_sq().sqrt()
+    }
+    pub fn arg(&self) -> f32 {
+        self.im.atan2(self.re)
+    }
+    pub fn inv(&self) -> Complex {
+        self.conj() / self.norm_sq()
+    }
+    /// The principal logarithm, with imaginary part in (-pi, pi].
+    pub fn ln(&self) -> Complex {
+        Complex::new(self.norm().ln(), self.arg())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl AddAssign for Complex {
+    fn add_assign(&mut self, rhs: Complex) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl SubAssign for Complex {
+    fn sub_assign(&mut self, rhs: Complex) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<f32> for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: f32) -> Complex {
+        Complex::new(self.re * rhs, self.im * rhs)
+    }
+}
+
+impl Mul<Complex> for f32 {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        rhs * self
+    }
+}
+
+impl Div<f32> for Complex {
+    type Output = Complex;
+    fn div(self, rhs: f32) -> Complex {
+        Complex::new(self.re / rhs, self.im / rhs)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex {
+            re: self.re * rhs.re - self.im * rhs.im,
+            im: self.re * rhs.im + self.im * rhs.re,
+        }
+    }
+}
+
+impl MulAssign for Complex {
+    fn mul_assign(&mut self, rhs: Complex) {
+        *self = *self * rhs;
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let norm_sq = rhs.norm_sq();
+        Complex {
+            re: (self.re * rhs.re + self.im * rhs.im) / norm_sq,
+            im: (self.im * rhs.re - self.re * rhs.im) / norm_sq,
+        }
+    }
+}
+
+impl DivAssign for Complex {
+    fn div_assign(&mut self, rhs: Complex) {
+        *self = *self / rhs;
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+impl From<f32> for Complex {
+    fn from(re: f32) -> Complex {
+        Complex::new(re, 0.0)
+    }
+}
+
+use std::fmt;
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} + {}i", self.re, self.im)
+    }
+}
@@ -1,5 +1,6 @@
 use std::ops::{Add, AddAssign, Mul};
 
+use crate::math::matrix::Mat4;
 use crate::math::quaternion::Quaternion;
 use crate::math::ray::Ray;
 use crate::math::vectors::Vec3;
@@ -166,3 +167,26 @@ impl Mul<AABBGeometry> for Quaternion {
         AABBGeometry::from_points(min, max)
     }
 }
+
+impl Mul<AABBGeometry> for Mat4 {
+    type Output = AABBGeometry;
+    fn mul(self, rhs: AABBGeometry) -> AABBGeometry {
+        let mut min = Vec3::all(f32::INFINITY);
+        let mut max = Vec3::all(f32::NEG_INFINITY);
+        for x_sign in &[-1.0, 1.0] {
+            for y_sign in &[-1.0, 1.0] {
+                for z_sign in &[-1.0, 1.0] {
+                    let corner = Vec3 {
+                        x: x_sign * rhs.extents.x,
+                        y: y_sign * rhs.extents.y,
+                        z: z_sign * rhs.extents.z,
+                    } + rhs.center;
+                    let moved = self.transform_point(corner);
+                    min = min.min(&moved);
+                    max = max.max(&moved);
+                }
+            }
+        }
+        AABBGeometry::from_points(min, max)
+    }
+}
@@ -0,0 +1,225 @@
+use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
+use crate::math::quaternion::Quaternion;
+use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::thurston::{bisect, GeodesicPiece, MarchHit, RayMarchable};
+use crate::math::vectors::Vec3;
+
+/// A solid box, `extents` out from its center along each of its axes, which
+/// are turned from the coordinate axes by `rotation`. Its faces are planes,
+/// so like triangles it is flat in every chart.
+///
+/// Unlike a union of rects, a ray starting inside hits the face it leaves
+/// through, so boxes make closed boundaries for volumes. The faces share one
+/// texture, laid out in a grid of three columns and two rows: the faces
+/// facing -x, +x and -y along the bottom, then -z, +z and +y.
+#[derive(Clone, Copy, Debug)]
+pub struct BoxGeometry {
+    pub center: Vec3,
+    pub extents: Vec3,
+    pub rotation: Quaternion,
+}
+
+impl BoxGeometry {
+    pub fn new(center: Vec3, extents: Vec3) -> BoxGeometry {
+        BoxGeometry::oriented(center, extents, Quaternion::identity())
+    }
+
+    pub fn oriented(center: Vec3, extents: Vec3, rotation: Quaternion) -> BoxGeometry {
+        BoxGeometry {
+            center,
+            extents: extents.abs(),
+            rotation,
+        }
+    }
+
+    /// A point in the box's own coordinates, where it runs from
+    /// `-extents` to `extents`.
+    fn local(&self, point: Vec3) -> Vec3 {
+        self.rotation.inv() * (point - self.center)
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        let local = self.local(point);
+        (0..3).all(|axis| local[axis].abs() <= self.extents[axis])
+    }
+
+    /// Where the ray crosses the boundary, as the parameter along the ray
+    /// and the axis of the face crossed. Rays starting inside cross where
+    /// they leave.
+    pub fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, usize)> {
+        let origin = self.local(ray.origin);
+        let direction = self.rotation.inv() * ray.direction;
+        let (mut near, mut far) = ((f32::NEG_INFINITY, 0), (f32::INFINITY, 0));
+        for axis in 0..3 {
+            if direction[axis].abs() < 1e-12 {
+                // Parallel to this pair of faces, so either always between
+                // them or never.
+                if origin[axis].abs() > self.extents[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let first = (-self.extents[axis] - origin[axis]) / direction[axis];
+            let second = (self.extents[axis] - origin[axis]) / direction[axis];
+            let (enter, leave) = if first < second {
+                (first, second)
+            } else {
+                (second, first)
+            };
+            if enter > near.0 {
+                near = (enter, axis);
+            }
+            if leave < far.0 {
+                far = (leave, axis);
+            }
+        }
+        if near.0 > far.0 {
+            return None;
+        }
+        [near, far]
+            .iter()
+            .copied()
+            .find(|&(t, _)| t >= t_min && t <= t_max)
+    }
+
+    /// The outward normal and texture coordinates where the box's own
+    /// coordinates put a point on the face across `axis`.
+    fn surface(&self, local: Vec3, axis: usize) -> (Vec3, f32, f32) {
+        let side = if local[axis] < 0.0 { 0 } else { 1 };
+        let sign = if side == 0 { -1.0 } else { 1.0 };
+        let normal = sign * [Vec3::right(), Vec3::up(), Vec3::forward()][axis];
+        let (first, second) = ((axis + 1) % 3, (axis + 2) % 3);
+        let along = |other: usize| 0.5 * (local[other] / self.extents[other] + 1.0);
+        // Faces go -x, +x, -y along the bottom row and -z, +z, +y above.
+        let cell = match (axis, side) {
+            (1, 1) => 5,
+            (2, side) => 3 + side,
+            (axis, side) => 2 * axis + side,
+        };
+        let u = ((cell % 3) as f32 + along(first).clamp(0.0, 1.0)) / 3.0;
+        let v = ((cell / 3) as f32 + along(second).clamp(0.0, 1.0)) / 2.0;
+        (self.rotation * normal, u, v)
+    }
+}
+
+impl RayCollidable for BoxGeometry {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        let (t, axis) = self.intersect(ray, t_min, t_max)?;
+        let location = ray.point_at_parameter(t);
+        let (normal, u, v) = self.surface(self.local(location), axis);
+        Some(RayHit {
+            hit_fraction: t,
+            location,
+            normal,
+            u,
+            v,
+            mirrored: ray.mirrored,
+            holonomy: Holonomy::new(),
+        })
+    }
+
+    fn bounding_box(&self, _t_min: f32, _t_max: f32) -> Option<AABBGeometry> {
+        let local = AABBGeometry {
+            center: Vec3::zero(),
+            extents: self.extents,
+        };
+        Some(self.rotation * local + self.center)
+    }
+}
+
+impl RayMarchable for BoxGeometry {
+    fn march_hit(&self, piece: GeodesicPiece, length: f32, _cast_time: f32) -> Option<MarchHit> {
+        // Positive outside the box and negative inside.
+        let outside = |local: Vec3| {
+            (0..3)
+                .map(|axis| local[axis].abs() - self.extents[axis])
+                .fold(f32::NEG_INFINITY, f32::max)
+        };
+        let distance = bisect(piece, length, |point| outside(self.local(point)))?;
+        let local = self.local(piece(distance));
+        let axis = (0..3)
+            .max_by(|&a, &b| {
+                let excess = |axis: usize| local[axis].abs() - self.extents[axis];
+                excess(a).total_cmp(&excess(b))
+            })
+            .unwrap();
+        let (gradient, u, v) = self.surface(local, axis);
+        Some(MarchHit {
+            distance,
+            gradient,
+            u,
+            v,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::colliders::Collider;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        (a - b).length() < 1e-5
+    }
+
+    #[test]
+    fn rays_from_outside_hit_where_they_enter() {
+        let cuboid = BoxGeometry::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(1.0, 2.0, 0.5));
+        let ray = Ray::new(Vec3::new(0.2, 0.3, 0.0), Vec3::forward());
+        let hit = cuboid.hit(&ray, 0.0, f32::MAX).unwrap();
+        assert!((hit.hit_fraction - 4.5).abs() < 1e-5);
+        assert!(close(hit.normal, -Vec3::forward()));
+        // Nothing is hit when the ray stops short or points away.
+        assert!(cuboid.hit(&ray, 0.0, 4.0).is_none());
+        let away = Ray::new(ray.origin, -Vec3::forward());
+        assert!(cuboid.hit(&away, 0.0, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn rays_from_inside_hit_where_they_leave() {
+        let cuboid = BoxGeometry::new(Vec3::zero(), Vec3::new(1.0, 2.0, 0.5));
+        let ray = Ray::new(Vec3::new(0.5, 0.0, 0.0), Vec3::up());
+        assert!(cuboid.contains(ray.origin));
+        let hit = cuboid.hit(&ray, 0.0, f32::MAX).unwrap();
+        assert!((hit.hit_fraction - 2.0).abs() < 1e-5);
+        assert!(close(hit.location, Vec3::new(0.5, 2.0, 0.0)));
+        assert!(close(hit.normal, Vec3::up()));
+    }
+
+    #[test]
+    fn boxes_bound_volumes() {
+        // Fog this thick scatters as soon as the ray is inside.
+        let cuboid = BoxGeometry::new(Vec3::zero(), Vec3::all(1.0));
+        let fog = Collider::from(cuboid).to_volume(1e5);
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -3.0), Vec3::forward());
+        let hit = fog.hit(&ray, 0.0, f32::MAX).unwrap();
+        assert!(hit.hit_fraction >= 2.0 && hit.hit_fraction < 2.01);
+        let inside = Ray::new(Vec3::zero(), Vec3::forward());
+        let hit = fog.hit(&inside, 0.0, f32::MAX).unwrap();
+        assert!(hit.hit_fraction < 0.01);
+    }
+
+    #[test]
+    fn rotated_boxes_are_bounded() {
+        let rotation = Quaternion::axis_angle(Vec3::forward(), std::f32::consts::FRAC_PI_4);
+        let extents = Vec3::new(1.0, 0.5, 0.25);
+        let center = Vec3::new(2.0, 0.0, 0.0);
+        let cuboid = BoxGeometry::oriented(center, extents, rotation);
+        let bounds = cuboid.bounding_box(0.0, 0.0).unwrap();
+        let diagonal = 1.5 * std::f32::consts::FRAC_1_SQRT_2;
+        assert!(close(bounds.center, center));
+        assert!(close(bounds.extents, Vec3::new(diagonal, diagonal, 0.25)));
+        for corner in 0..8 {
+            let sign = |bit: usize| if corner & (1 << bit) == 0 { -1.0 } else { 1.0 };
+            let local = Vec3::new(
+                sign(0) * extents.x,
+                sign(1) * extents.y,
+                sign(2) * extents.z,
+            );
+            let point = rotation * local + center;
+            let offset = point - bounds.center;
+            assert!((0..3).all(|axis| offset[axis].abs() <= bounds.extents[axis] + 1e-5));
+        }
+    }
+}
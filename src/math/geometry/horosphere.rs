@@ -0,0 +1,108 @@
+use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
+use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::space::Geometry;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// A horoball of hyperbolic space: the limit of balls whose centers run off
+/// to an ideal point. In the hyperboloid model it is the set of `X` with
+/// `-<X, N> <= 1` for a light-like vector `N` pointing at the ideal point,
+/// and in the Klein model it is an ellipsoid touching the sphere at
+/// infinity there. Its boundary, the horosphere, is flat.
+///
+/// Horospheres only exist in hyperbolic space, so rays travelling through any
+/// other geometry never hit one.
+#[derive(Clone, Copy, Debug)]
+pub struct HorosphereGeometry {
+    pub pole: Vec4,
+}
+
+impl HorosphereGeometry {
+    /// The horosphere about the ideal `point` (on the unit sphere) which
+    /// passes `distance` from the origin of the chart. The distance is
+    /// negative when the origin is inside the horoball.
+    pub fn new(point: Vec3, distance: f32) -> HorosphereGeometry {
+        HorosphereGeometry::from_pole(Vec4::point(point.normalized()) * distance.exp())
+    }
+
+    pub fn from_pole(pole: Vec4) -> HorosphereGeometry {
+        HorosphereGeometry { pole }
+    }
+
+    /// The ideal point the horosphere is centered on.
+    pub fn ideal_point(&self) -> Vec3 {
+        self.pole.dehomogenize()
+    }
+}
+
+impl RayCollidable for HorosphereGeometry {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        if ray.geometry != Geometry::Hyperbolic {
+            return None;
+        }
+        // With X the lift of x = o + t d the horosphere is where
+        // (N.w - x . N.xyz)^2 = 1 - |x|^2, which is quadratic in t.
+        let normal = self.pole.xyz();
+        let along = self.pole.w - ray.origin.dot(&normal);
+        let speed = ray.direction.dot(&normal);
+        let a = speed * speed + ray.direction.length_sq();
+        let b = ray.origin.dot(&ray.direction) - along * speed;
+        let c = along * along + ray.origin.length_sq() - 1.0;
+        let descriminant = b * b - a * c;
+        if descriminant <= 0.0 {
+            return None;
+        }
+        let sqrt_descriminant = descriminant.sqrt();
+        let time = [(-b - sqrt_descriminant) / a, (-b + sqrt_descriminant) / a]
+            .iter()
+            .copied()
+            .find(|&time| t_min <= time && time <= t_max)?;
+        let location = ray.point_at_parameter(time);
+
+        // The outward normal in the model is the part of -N tangent to the
+        // hyperboloid at X, whose spatial part is the normal in the chart.
+        let geometry = Geometry::Hyperbolic;
+        let point = geometry.lift(location);
+        let outward = -(self.pole + geometry.inner(&point, &self.pole) * point);
+
+        // Writing X = a N + b N' + y, with N' pointing at the opposite ideal
+        // point, the part y orthogonal to both is an isometry from the
+        // horosphere onto a Euclidean plane. Its coordinates are used as they
+        // are, so textures which repeat tile the horosphere.
+        let axis = normal.normalized();
+        let helper = if axis.x.abs() < 0.9 {
+            Vec3::right()
+        } else {
+            Vec3::up()
+        };
+        let first = axis.cross(&helper).normalized();
+        let second = axis.cross(&first);
+        Some(RayHit {
+            hit_fraction: time,
+            location,
+            normal: outward.xyz().normalized(),
+            u: point.xyz().dot(&first),
+            v: point.xyz().dot(&second),
+            mirrored: ray.mirrored,
+            holonomy: Holonomy::new(),
+        })
+    }
+
+    fn bounding_box(&self, _t_min: f32, _t_max: f32) -> Option<AABBGeometry> {
+        // In the chart the horoball is an ellipsoid of revolution about the
+        // line to its ideal point, reaching from (k^2 - 1) / (k^2 + 1) to 1
+        // along it, where k = N.w, and 1 / sqrt(k^2 + 1) across.
+        let axis = self.ideal_point();
+        let k_sq = self.pole.w * self.pole.w;
+        let along = 1.0 / (k_sq + 1.0);
+        let across_sq = along;
+        let extent = |component: f32| {
+            let component_sq = component * component;
+            (along * along * component_sq + across_sq * (1.0 - component_sq)).sqrt()
+        };
+        Some(AABBGeometry {
+            center: axis * (k_sq * along),
+            extents: Vec3::new(extent(axis.x), extent(axis.y), extent(axis.z)),
+        })
+    }
+}
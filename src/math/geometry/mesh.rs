@@ -0,0 +1,290 @@
+use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::geometry::triangle::TriangleGeometry;
+use crate::math::manifold::holonomy::Holonomy;
+use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::vectors::Vec3;
+
+/// How many triangles a leaf of a mesh's hierarchy holds at most.
+const LEAF_SIZE: usize = 4;
+
+/// Room for the nodes waiting to be visited by a ray. Splits halve the
+/// triangles, so the hierarchy is at most log2 of their count deep, and the
+/// stack never holds more than one node per level plus one.
+const STACK_SIZE: usize = 64;
+
+#[derive(Clone, Debug)]
+enum MeshNode {
+    /// The triangles `order[start..end]`.
+    Leaf {
+        bounds: AABBGeometry,
+        start: usize,
+        end: usize,
+    },
+    /// The left child follows directly after the split, the right child is
+    /// at `right`.
+    Split { bounds: AABBGeometry, right: usize },
+}
+
+/// A mesh of triangles sharing their corners, with its own bounding volume
+/// hierarchy so that a large model is a single collider. Normals and texture
+/// coordinates may be given per corner, and are interpolated across each
+/// triangle. Without normals the triangles are flat shaded, and without
+/// texture coordinates the barycentric coordinates on each triangle are used.
+#[derive(Clone, Debug)]
+pub struct MeshGeometry {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<(f32, f32)>,
+    pub triangles: Vec<[usize; 3]>,
+    /// The triangles in the order the hierarchy's leaves refer to them.
+    order: Vec<usize>,
+    nodes: Vec<MeshNode>,
+}
+
+impl MeshGeometry {
+    pub fn new(positions: Vec<Vec3>, triangles: Vec<[usize; 3]>) -> MeshGeometry {
+        for triangle in &triangles {
+            assert!(
+                triangle.iter().all(|&corner| corner < positions.len()),
+                "Mesh triangle refers to a missing vertex!"
+            );
+        }
+        let mut mesh = MeshGeometry {
+            positions,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            order: (0..triangles.len()).collect(),
+            triangles,
+            nodes: Vec::new(),
+        };
+        let bounds: Vec<AABBGeometry> = (0..mesh.triangles.len())
+            .map(|index| mesh.triangle(index).bounding_box(0.0, 0.0).unwrap())
+            .collect();
+        let mut order = std::mem::take(&mut mesh.order);
+        let count = order.len();
+        if count > 0 {
+            mesh.build(&bounds, &mut order, 0, count);
+        }
+        mesh.order = order;
+        mesh
+    }
+
+    /// Give every vertex a normal.
+    pub fn with_normals(self, normals: Vec<Vec3>) -> MeshGeometry {
+        assert_eq!(
+            normals.len(),
+            self.positions.len(),
+            "Mesh needs one normal per vertex!"
+        );
+        MeshGeometry { normals, ..self }
+    }
+
+    /// Give every vertex texture coordinates.
+    pub fn with_uvs(self, uvs: Vec<(f32, f32)>) -> MeshGeometry {
+        assert_eq!(
+            uvs.len(),
+            self.positions.len(),
+            "Mesh needs one set of texture coordinates per vertex!"
+        );
+        MeshGeometry { uvs, ..self }
+    }
+
+    /// Give every vertex the average of the normals of the triangles around
+    /// it, weighted by their areas, so the mesh is smooth shaded.
+    pub fn with_smooth_normals(self) -> MeshGeometry {
+        let mut normals = vec![Vec3::zero(); self.positions.len()];
+        for &[a, b, c] in &self.triangles {
+            let (pa, pb, pc) = (self.positions[a], self.positions[b], self.positions[c]);
+            let area_normal = (pb - pa).cross(&(pc - pa));
+            for &corner in &[a, b, c] {
+                normals[corner] += area_normal;
+            }
+        }
+        let normals = normals
+            .into_iter()
+            .map(|normal| {
+                if normal.length_sq() > 0.0 {
+                    normal.normalized()
+                } else {
+                    normal
+                }
+            })
+            .collect();
+        self.with_normals(normals)
+    }
+
+    pub fn triangle(&self, index: usize) -> TriangleGeometry {
+        let [a, b, c] = self.triangles[index];
+        TriangleGeometry::new(self.positions[a], self.positions[b], self.positions[c])
+    }
+
+    /// Build the node for `order[start..end]`, splitting it in half along
+    /// the axis its triangles' centers spread furthest along.
+    fn build(&mut self, bounds: &[AABBGeometry], order: &mut [usize], start: usize, end: usize) {
+        let total = order[start..end]
+            .iter()
+            .skip(1)
+            .fold(bounds[order[start]], |total, &index| total + bounds[index]);
+        if end - start <= LEAF_SIZE {
+            self.nodes.push(MeshNode::Leaf {
+                bounds: total,
+                start,
+                end,
+            });
+            return;
+        }
+        let (low, high) = order[start..end].iter().fold(
+            (Vec3::all(f32::MAX), Vec3::all(f32::MIN)),
+            |(low, high), &index| {
+                (
+                    low.min(&bounds[index].center),
+                    high.max(&bounds[index].center),
+                )
+            },
+        );
+        let spread = high - low;
+        let axis = if spread.x > spread.y && spread.x > spread.z {
+            0
+        } else if spread.y > spread.z {
+            1
+        } else {
+            2
+        };
+        order[start..end].sort_by(|&a, &b| {
+            bounds[a].center[axis]
+                .partial_cmp(&bounds[b].center[axis])
+                .unwrap_or(std::cmp::Ordering::Less)
+        });
+        let middle = (start + end) / 2;
+        let node = self.nodes.len();
+        self.nodes.push(MeshNode::Split {
+            bounds: total,
+            right: 0,
+        });
+        self.build(bounds, order, start, middle);
+        let right_index = self.nodes.len();
+        if let MeshNode::Split { ref mut right, .. } = self.nodes[node] {
+            *right = right_index;
+        }
+        self.build(bounds, order, middle, end);
+    }
+
+    /// The hit on the triangle at `index`, given where the ray crossed it.
+    fn hit_on(&self, ray: &Ray, index: usize, t: f32, s: f32, r: f32) -> RayHit {
+        let [a, b, c] = self.triangles[index];
+        let blend = |x: Vec3, y: Vec3, z: Vec3| (1.0 - s - r) * x + s * y + r * z;
+        let normal = if self.normals.is_empty() {
+            self.triangle(index).normal()
+        } else {
+            blend(self.normals[a], self.normals[b], self.normals[c]).normalized()
+        };
+        let (u, v) = if self.uvs.is_empty() {
+            (s, r)
+        } else {
+            let (ua, va) = self.uvs[a];
+            let (ub, vb) = self.uvs[b];
+            let (uc, vc) = self.uvs[c];
+            (
+                (1.0 - s - r) * ua + s * ub + r * uc,
+                (1.0 - s - r) * va + s * vb + r * vc,
+            )
+        };
+        RayHit {
+            hit_fraction: t,
+            location: ray.point_at_parameter(t),
+            normal,
+            u,
+            v,
+            mirrored: ray.mirrored,
+            holonomy: Holonomy::new(),
+        }
+    }
+}
+
+impl RayCollidable for MeshGeometry {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut closest: Option<(usize, f32, f32, f32)> = None;
+        let mut t_max = t_max;
+        let mut stack = [0; STACK_SIZE];
+        let mut pending = 1;
+        while pending > 0 {
+            pending -= 1;
+            let node = stack[pending];
+            match self.nodes[node] {
+                MeshNode::Leaf { bounds, start, end } => {
+                    if !bounds.overlaps(ray, t_min, t_max) {
+                        continue;
+                    }
+                    for &index in &self.order[start..end] {
+                        if let Some((t, s, r)) = self.triangle(index).intersect(ray, t_min, t_max) {
+                            t_max = t;
+                            closest = Some((index, t, s, r));
+                        }
+                    }
+                }
+                MeshNode::Split { bounds, right } => {
+                    if bounds.overlaps(ray, t_min, t_max) {
+                        stack[pending] = right;
+                        stack[pending + 1] = node + 1;
+                        pending += 2;
+                    }
+                }
+            }
+        }
+        closest.map(|(index, t, s, r)| self.hit_on(ray, index, t, s, r))
+    }
+
+    fn bounding_box(&self, _t_min: f32, _t_max: f32) -> Option<AABBGeometry> {
+        match self.nodes.first() {
+            Some(MeshNode::Leaf { bounds, .. }) | Some(MeshNode::Split { bounds, .. }) => {
+                Some(*bounds)
+            }
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hierarchy_finds_the_closest_triangle() {
+        // A crumpled sheet of triangles, deep enough to need many levels.
+        let size = 100;
+        let height = |x: usize, z: usize| ((x * 7 + z * 13) % 11) as f32 * 0.05;
+        let mut positions = Vec::new();
+        for z in 0..=size {
+            for x in 0..=size {
+                positions.push(Vec3::new(x as f32, height(x, z), z as f32));
+            }
+        }
+        let corner = |x: usize, z: usize| z * (size + 1) + x;
+        let mut triangles = Vec::new();
+        for z in 0..size {
+            for x in 0..size {
+                triangles.push([corner(x, z), corner(x + 1, z), corner(x, z + 1)]);
+                triangles.push([corner(x + 1, z), corner(x + 1, z + 1), corner(x, z + 1)]);
+            }
+        }
+        let mesh = MeshGeometry::new(positions, triangles);
+
+        for index in 0..200 {
+            let along = index as f32 * 0.49;
+            let origin = Vec3::new(along % 97.0 + 0.3, 3.0, (along * 1.7) % 97.0 + 0.6);
+            let direction = Vec3::new(0.3 * (index as f32).sin(), -1.0, 0.2);
+            let ray = Ray::new(origin, direction.normalized());
+            let brute = (0..mesh.triangles.len())
+                .filter_map(|triangle| mesh.triangle(triangle).intersect(&ray, 0.0, f32::MAX))
+                .map(|(t, _, _)| t)
+                .fold(None, |best: Option<f32>, t| {
+                    Some(best.map_or(t, |best| best.min(t)))
+                });
+            let hit = mesh.hit(&ray, 0.0, f32::MAX).map(|hit| hit.hit_fraction);
+            assert_eq!(hit, brute);
+        }
+    }
+}
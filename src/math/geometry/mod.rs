@@ -1,4 +1,10 @@
 pub mod aabb;
+pub mod cuboid;
+pub mod horosphere;
+pub mod mesh;
+pub mod plane;
 pub mod rect;
 pub mod sphere;
+pub mod triangle;
+pub mod tube;
 pub mod volumes;
@@ -0,0 +1,27 @@
+use crate::math::vectors::Vec3;
+
+/// The plane `normal . x = offset`. The normal points out of whatever the
+/// plane is bounding.
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub offset: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vec3, offset: f32) -> Plane {
+        Plane { normal, offset }
+    }
+
+    pub fn through(point: Vec3, normal: Vec3) -> Plane {
+        Plane {
+            normal,
+            offset: normal.dot(&point),
+        }
+    }
+
+    /// Positive on the side the normal points towards.
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(&point) - self.offset
+    }
+}
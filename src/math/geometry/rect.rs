@@ -1,5 +1,7 @@
 use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
 use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::thurston::{bisect, GeodesicPiece, MarchHit, RayMarchable};
 use crate::math::vectors::Vec3;
 
 #[derive(Clone, Copy, Debug)]
@@ -46,6 +48,8 @@ impl RayCollidable for RectGeometry {
             normal: -Vec3::forward(),
             u: off.x / self.width + 0.5,
             v: off.y / self.height + 0.5,
+            mirrored: ray.mirrored,
+            holonomy: Holonomy::new(),
         })
     }
 
@@ -56,3 +60,19 @@ impl RayCollidable for RectGeometry {
         })
     }
 }
+
+impl RayMarchable for RectGeometry {
+    fn march_hit(&self, piece: GeodesicPiece, length: f32, _cast_time: f32) -> Option<MarchHit> {
+        let distance = bisect(piece, length, |point| point.z - self.center.z)?;
+        let off = piece(distance) - self.center;
+        if off.x.abs() >= self.width / 2.0 || off.y.abs() >= self.height / 2.0 {
+            return None;
+        }
+        Some(MarchHit {
+            distance,
+            gradient: -Vec3::forward(),
+            u: off.x / self.width + 0.5,
+            v: off.y / self.height + 0.5,
+        })
+    }
+}
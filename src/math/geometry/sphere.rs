@@ -1,7 +1,11 @@
 use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
 use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::space::Geometry;
+use crate::math::thurston::{bisect, GeodesicPiece, MarchHit, RayMarchable};
 use crate::math::vectors::Vec3;
 
+#[derive(Clone, Copy, Debug)]
 pub struct SphereGeometry {
     pub center: Vec3,
     pub radius: f32,
@@ -20,6 +24,72 @@ impl SphereGeometry {
             radius: self.radius,
         }
     }
+
+    /// Intersect with the sphere of `radius` about `center` in a curved
+    /// geometry. The ray is followed as a unit speed geodesic `X(t)` in the
+    /// model, and the hit solves `<X(t), C> = cos(radius)` on the 3-sphere or
+    /// `-<X(t), C> = cosh(radius)` in hyperbolic space.
+    fn hit_curved(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        let geometry = ray.geometry;
+        let geodesic = geometry.geodesic(ray);
+        let center = geometry.lift(self.center);
+        let radius = self.radius.abs();
+        let a = geometry.inner(&geodesic.position, &center);
+        let b = geometry.inner(&geodesic.tangent, &center);
+        let c = geometry.cosine(radius);
+        let distances = match geometry {
+            Geometry::Hyperbolic => {
+                // With x = e^t this is (a + b) x^2 + 2 c x + (a - b) = 0.
+                let descriminant = c * c - (a + b) * (a - b);
+                if descriminant <= 0.0 {
+                    return None;
+                }
+                let sqrt_descriminant = descriminant.sqrt();
+                [
+                    ((c - sqrt_descriminant) / -(a + b)).ln(),
+                    ((c + sqrt_descriminant) / -(a + b)).ln(),
+                ]
+            }
+            _ => {
+                // a cos(t) + b sin(t) = |(a, b)| cos(t - phase).
+                let amplitude = (a * a + b * b).sqrt();
+                if amplitude <= c.abs() {
+                    return None;
+                }
+                let phase = b.atan2(a);
+                let offset = (c / amplitude).acos();
+                [phase - offset, phase + offset]
+            }
+        };
+        // Only the points in front of the chart's hemisphere are in view.
+        let (time, point) = distances
+            .iter()
+            .map(|&distance| geodesic.at(geometry, distance))
+            .filter(|point| point.w > 0.0)
+            .map(|point| (geometry.chart_parameter(ray, point), point))
+            .filter(|&(time, _)| t_min <= time && time <= t_max)
+            .min_by(|x, y| x.0.partial_cmp(&y.0).unwrap())?;
+        // The tangent plane of the sphere at the hit is the plane dual to the
+        // outward normal of the model, which in the chart has normal N.xyz.
+        let normal = (c * point - center) * (self.radius.signum() / geometry.sine(radius));
+        let rel_point = geometry
+            .translation_inverse(self.center)
+            .transform_point(point.dehomogenize())
+            .normalized();
+        let phi = rel_point.z.atan2(rel_point.x);
+        let theta = rel_point.y.asin();
+        use std::f32::consts::FRAC_PI_2;
+        use std::f32::consts::PI;
+        Some(RayHit {
+            hit_fraction: time,
+            location: ray.point_at_parameter(time),
+            normal: normal.xyz().normalized(),
+            u: 1.0 - (phi + PI) / (2.0 * PI),
+            v: (theta + FRAC_PI_2) / PI,
+            mirrored: ray.mirrored,
+            holonomy: Holonomy::new(),
+        })
+    }
 }
 
 fn smallest_bounded(a: f32, b: f32, min: f32, max: f32) -> Option<f32> {
@@ -33,6 +103,9 @@ fn smallest_bounded(a: f32, b: f32, min: f32, max: f32) -> Option<f32> {
 
 impl RayCollidable for SphereGeometry {
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        if ray.geometry != Geometry::Euclidean {
+            return self.hit_curved(ray, t_min, t_max);
+        }
         let offset = ray.origin - self.center;
         let a = ray.direction.length_sq();
         let b = 2.0f32 * offset.dot(&ray.direction);
@@ -60,6 +133,8 @@ impl RayCollidable for SphereGeometry {
                     normal: (location - self.center) / self.radius,
                     u: 1.0 - (phi + PI) / (2.0 * PI),
                     v: (theta + FRAC_PI_2) / PI,
+                    mirrored: ray.mirrored,
+                    holonomy: Holonomy::new(),
                 })
             } else {
                 None
@@ -70,6 +145,8 @@ impl RayCollidable for SphereGeometry {
     }
 
     fn bounding_box(&self, _t_min: f32, _t_max: f32) -> Option<AABBGeometry> {
+        // The Klein model never stretches distances, so this also bounds a
+        // hyperbolic sphere of the same radius.
         let radius = Vec3::all(self.radius.abs());
         Some(AABBGeometry {
             center: self.center,
@@ -77,3 +154,25 @@ impl RayCollidable for SphereGeometry {
         })
     }
 }
+
+impl RayMarchable for SphereGeometry {
+    /// The sphere is the one of this radius in coordinates, which is not in
+    /// general a ball of the geometry.
+    fn march_hit(&self, piece: GeodesicPiece, length: f32, _cast_time: f32) -> Option<MarchHit> {
+        let radius = self.radius.abs();
+        let distance = bisect(piece, length, |point| {
+            (point - self.center).length() - radius
+        })?;
+        let rel_point = (piece(distance) - self.center).normalized();
+        let phi = rel_point.z.atan2(rel_point.x);
+        let theta = rel_point.y.asin();
+        use std::f32::consts::FRAC_PI_2;
+        use std::f32::consts::PI;
+        Some(MarchHit {
+            distance,
+            gradient: rel_point * self.radius.signum(),
+            u: 1.0 - (phi + PI) / (2.0 * PI),
+            v: (theta + FRAC_PI_2) / PI,
+        })
+    }
+}
@@ -0,0 +1,77 @@
+use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
+use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::vectors::Vec3;
+
+/// A flat triangle. Triangles are flat in every chart, so they are totally
+/// geodesic in curved geometries too. The front is the side the corners go
+/// anticlockwise around, which the normal points out of.
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleGeometry {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+}
+
+impl TriangleGeometry {
+    pub fn new(a: Vec3, b: Vec3, c: Vec3) -> TriangleGeometry {
+        TriangleGeometry { a, b, c }
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        (self.b - self.a).cross(&(self.c - self.a)).normalized()
+    }
+
+    /// Where the ray crosses the triangle, as the parameter along the ray
+    /// and the barycentric weights of `b` and `c` there.
+    pub fn intersect(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+        // Moller-Trumbore: solve o + t d = a + s (b - a) + r (c - a).
+        let first = self.b - self.a;
+        let second = self.c - self.a;
+        let across = ray.direction.cross(&second);
+        let determinant = first.dot(&across);
+        if determinant.abs() < 1e-12 {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let offset = ray.origin - self.a;
+        let s = offset.dot(&across) * inverse;
+        if !(0.0..=1.0).contains(&s) {
+            return None;
+        }
+        let up = offset.cross(&first);
+        let r = ray.direction.dot(&up) * inverse;
+        if r < 0.0 || s + r > 1.0 {
+            return None;
+        }
+        let t = second.dot(&up) * inverse;
+        if t < t_min || t > t_max {
+            return None;
+        }
+        Some((t, s, r))
+    }
+}
+
+impl RayCollidable for TriangleGeometry {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        let (t, s, r) = self.intersect(ray, t_min, t_max)?;
+        Some(RayHit {
+            hit_fraction: t,
+            location: ray.point_at_parameter(t),
+            normal: self.normal(),
+            u: s,
+            v: r,
+            mirrored: ray.mirrored,
+            holonomy: Holonomy::new(),
+        })
+    }
+
+    fn bounding_box(&self, _t_min: f32, _t_max: f32) -> Option<AABBGeometry> {
+        // Padded so triangles lying in an axis plane still have some depth.
+        let padding = Vec3::all(0.0001);
+        Some(AABBGeometry::from_points(
+            self.a.min(&self.b).min(&self.c) - padding,
+            self.a.max(&self.b).max(&self.c) + padding,
+        ))
+    }
+}
@@ -0,0 +1,151 @@
+use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
+use crate::math::ray::{Ray, RayCollidable, RayHit};
+use crate::math::space::Geometry;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// The points within `radius` of the geodesic segment from `start` to `end`,
+/// without end caps. Good for drawing paths through a space as thin lines.
+#[derive(Clone, Copy, Debug)]
+pub struct TubeGeometry {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+}
+
+impl TubeGeometry {
+    pub fn new(start: Vec3, end: Vec3, radius: f32) -> TubeGeometry {
+        TubeGeometry { start, end, radius }
+    }
+
+    fn hit_flat(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        let axis = self.end - self.start;
+        let length_sq = axis.length_sq();
+        // Drop the parts of everything along the axis.
+        let flatten = |vector: Vec3| vector - axis * (vector.dot(&axis) / length_sq);
+        let offset = flatten(ray.origin - self.start);
+        let direction = flatten(ray.direction);
+        let a = direction.length_sq();
+        let b = offset.dot(&direction);
+        let c = offset.length_sq() - self.radius * self.radius;
+        let descriminant = b * b - a * c;
+        if a <= 0.0 || descriminant <= 0.0 {
+            return None;
+        }
+        let sqrt_descriminant = descriminant.sqrt();
+        [(-b - sqrt_descriminant) / a, (-b + sqrt_descriminant) / a]
+            .iter()
+            .copied()
+            .filter(|&time| t_min <= time && time <= t_max)
+            .find_map(|time| {
+                let location = ray.point_at_parameter(time);
+                let along = (location - self.start).dot(&axis) / length_sq;
+                if !(0.0..=1.0).contains(&along) {
+                    return None;
+                }
+                Some(RayHit {
+                    hit_fraction: time,
+                    location,
+                    normal: flatten(location - self.start) / self.radius,
+                    u: along,
+                    v: 0.5,
+                    mirrored: ray.mirrored,
+                    holonomy: Holonomy::new(),
+                })
+            })
+    }
+
+    /// In a curved geometry the distance `d` from `X` to the geodesic
+    /// through `A` and `B` comes from projecting `X` onto their span: the
+    /// projection `P` has `<P, P> = cos(d)^2 <X, X>`, or `cosh(d)^2` in
+    /// hyperbolic space. Along a ray that is a quadratic in the parameter.
+    fn hit_curved(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        let geometry = ray.geometry;
+        let (a, b) = (geometry.lift(self.start), geometry.lift(self.end));
+        let level = geometry.cosine(self.radius).powi(2);
+        // The inverse of the Gram matrix of A and B.
+        let (aa, ab, bb) = (
+            geometry.inner(&a, &a),
+            geometry.inner(&a, &b),
+            geometry.inner(&b, &b),
+        );
+        let determinant = aa * bb - ab * ab;
+        let project = |x: &Vec4| {
+            let (xa, xb) = (geometry.inner(x, &a), geometry.inner(x, &b));
+            (
+                (bb * xa - ab * xb) / determinant,
+                (aa * xb - ab * xa) / determinant,
+            )
+        };
+        // F(X) = <P, P> - level <X, X> as a quadratic form, evaluated on
+        // X = O + t D.
+        let form = |x: &Vec4, y: &Vec4| {
+            let (xa, xb) = (geometry.inner(x, &a), geometry.inner(x, &b));
+            let (ya, yb) = (geometry.inner(y, &a), geometry.inner(y, &b));
+            (bb * xa * ya - ab * (xa * yb + xb * ya) + aa * xb * yb) / determinant
+                - level * geometry.inner(x, y)
+        };
+        let origin = Vec4::point(ray.origin);
+        let direction = Vec4::direction(ray.direction);
+        let qa = form(&direction, &direction);
+        let qb = form(&origin, &direction);
+        let qc = form(&origin, &origin);
+        let descriminant = qb * qb - qa * qc;
+        if qa == 0.0 || descriminant <= 0.0 {
+            return None;
+        }
+        let sqrt_descriminant = descriminant.sqrt();
+        let mut times = [
+            (-qb - sqrt_descriminant) / qa,
+            (-qb + sqrt_descriminant) / qa,
+        ];
+        times.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        times
+            .iter()
+            .copied()
+            .filter(|&time| t_min <= time && time <= t_max)
+            .find_map(|time| {
+                let location = ray.point_at_parameter(time);
+                let point = geometry.lift(location);
+                // Only the part of the tube beside the segment, where the
+                // projection is a positive combination of the ends.
+                let (along_a, along_b) = project(&point);
+                if along_a < 0.0 || along_b < 0.0 {
+                    return None;
+                }
+                let projection = a * along_a + b * along_b;
+                let outward = point * level - projection;
+                Some(RayHit {
+                    hit_fraction: time,
+                    location,
+                    normal: outward.xyz().normalized(),
+                    u: along_b / (along_a + along_b),
+                    v: 0.5,
+                    mirrored: ray.mirrored,
+                    holonomy: Holonomy::new(),
+                })
+            })
+    }
+}
+
+impl RayCollidable for TubeGeometry {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<RayHit> {
+        match ray.geometry {
+            Geometry::Euclidean => self.hit_flat(ray, t_min, t_max),
+            _ => self.hit_curved(ray, t_min, t_max),
+        }
+    }
+
+    fn bounding_box(&self, _t_min: f32, _t_max: f32) -> Option<AABBGeometry> {
+        // As for spheres the Klein model never stretches distances, but the
+        // gnomonic chart stretches them by up to 1 + r^2 at a distance r out.
+        let reach_sq = self.start.length_sq().max(self.end.length_sq());
+        let padding = Vec3::all(self.radius * (1.0 + reach_sq));
+        let low = self.start.min(&self.end) - padding;
+        let high = self.start.max(&self.end) + padding;
+        Some(AABBGeometry {
+            center: 0.5 * (low + high),
+            extents: 0.5 * (high - low),
+        })
+    }
+}
@@ -1,4 +1,5 @@
 use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
 use crate::math::ray::{Ray, RayCollidable, RayHit};
 
 use rand::distributions::Uniform;
@@ -46,18 +47,27 @@ impl RayCollidable for ConstantVolume {
                 }
                 // Clamp to initial position of ray
                 let t_enter = t_enter.max(0.0);
-                let direction_length = ray.direction.length();
-                let distance_in_boundary = (t_exit - t_enter) * direction_length;
+                // The fog thins light out by how far it really travels, which
+                // in a curved geometry is not the distance in the chart.
+                let geometry = ray.geometry;
+                let entry = Ray {
+                    origin: ray.point_at_parameter(t_enter),
+                    ..*ray
+                };
+                let distance_in_boundary =
+                    geometry.distance(entry.origin, ray.point_at_parameter(t_exit));
 
                 let hit_distance = -(1.0 / self.density) * rng.sample(between).ln();
                 if hit_distance <= distance_in_boundary {
-                    let hit_time = t_enter + hit_distance / direction_length;
+                    let hit_time = t_enter + geometry.parameter_at_distance(&entry, hit_distance);
                     let ray_hit = RayHit {
                         hit_fraction: hit_time,
                         location: ray.point_at_parameter(hit_time),
                         normal: UnitSphere.sample(&mut rng).into(),
                         u: 0.5 * (enter_hit.u + exit_hit.u),
                         v: 0.5 * (enter_hit.v + exit_hit.v),
+                        mirrored: ray.mirrored,
+                        holonomy: Holonomy::new(),
                     };
                     if debug_output {
                         println!("Hit Distance: {} / {}", hit_distance, distance_in_boundary);
@@ -0,0 +1,248 @@
+use crate::math::geometry::plane::Plane;
+use crate::math::manifold::triangulation::Triangulation;
+use crate::math::matrix::Mat4;
+use crate::math::ray::Ray;
+use crate::math::space::Geometry;
+use crate::math::vectors::Vec3;
+
+/// Where a ray leaving through a face ends up.
+#[derive(Clone, Copy, Debug)]
+pub struct FaceGluing {
+    pub cell: usize,
+    pub face: usize,
+    /// Takes the local coordinates of this cell to those of `cell`.
+    pub isometry: Mat4,
+}
+
+impl FaceGluing {
+    /// Glue `face` of `cell` to itself by reflecting across its plane, making
+    /// it a mirror of an orbifold. Rays reaching a mirror bounce straight
+    /// back into the cell, handedness reversed.
+    pub fn mirror(geometry: Geometry, cell: usize, face: usize, plane: &Plane) -> FaceGluing {
+        FaceGluing {
+            cell,
+            face,
+            isometry: geometry.reflection(plane),
+        }
+    }
+
+    /// Whether this is the gluing on `face` of `cell` which sends the face
+    /// back onto itself.
+    pub fn is_mirror(&self, cell: usize, face: usize) -> bool {
+        self.cell == cell && self.face == face
+    }
+
+    /// Whether passing through the gluing turns left handed things into
+    /// right handed ones, as in a Klein bottle.
+    pub fn reverses_orientation(&self) -> bool {
+        self.isometry.determinant() < 0.0
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct CellFace {
+    pub plane: Plane,
+    pub gluing: Option<FaceGluing>,
+}
+
+/// A convex cell bounded by planes in its own local chart.
+#[derive(Clone, Debug)]
+pub struct Cell {
+    pub faces: Vec<CellFace>,
+}
+
+impl Cell {
+    pub fn contains(&self, point: Vec3) -> bool {
+        self.faces
+            .iter()
+            .all(|face| face.plane.signed_distance(point) <= 0.0001)
+    }
+
+    /// Find the face a ray starting inside the cell leaves through and the
+    /// parameter it leaves at. The face the ray `entered` through is skipped
+    /// so a freshly transported ray does not immediately leave again.
+    pub fn exit(&self, ray: &Ray, entered: Option<usize>) -> Option<(f32, usize)> {
+        let mut exit: Option<(f32, usize)> = None;
+        for (index, face) in self.faces.iter().enumerate() {
+            if Some(index) == entered {
+                continue;
+            }
+            let speed = face.plane.normal.dot(&ray.direction);
+            if speed <= 1e-6 {
+                continue;
+            }
+            // Rays which started slightly outside due to rounding leave at 0.
+            let t = (-face.plane.signed_distance(ray.origin) / speed).max(0.0);
+            if exit.is_none_or(|(best, _)| t < best) {
+                exit = Some((t, index));
+            }
+        }
+        exit
+    }
+
+    /// The corners of the cell, as the points where three or more of its
+    /// planes meet, along with the faces meeting there.
+    pub fn vertices(&self) -> Vec<(Vec3, Vec<usize>)> {
+        let mut vertices: Vec<(Vec3, Vec<usize>)> = Vec::new();
+        let count = self.faces.len();
+        for first in 0..count {
+            for second in first + 1..count {
+                for third in second + 1..count {
+                    let [a, b, c] = [first, second, third].map(|face| self.faces[face].plane);
+                    let volume = a.normal.dot(&b.normal.cross(&c.normal));
+                    if volume.abs() < 1e-6 {
+                        continue;
+                    }
+                    let point = (a.offset * b.normal.cross(&c.normal)
+                        + b.offset * c.normal.cross(&a.normal)
+                        + c.offset * a.normal.cross(&b.normal))
+                        / volume;
+                    if !self.contains(point)
+                        || vertices
+                            .iter()
+                            .any(|(seen, _)| (*seen - point).length() < 1e-4)
+                    {
+                        continue;
+                    }
+                    let faces = (0..count)
+                        .filter(|&face| self.faces[face].plane.signed_distance(point).abs() < 1e-4)
+                        .collect();
+                    vertices.push((point, faces));
+                }
+            }
+        }
+        vertices
+    }
+
+    /// The edges of the cell, as the corners at either end. Two corners are
+    /// joined by an edge when two faces meet at both of them.
+    pub fn edges(&self) -> Vec<(Vec3, Vec3)> {
+        let vertices = self.vertices();
+        let mut edges = Vec::new();
+        for (index, (start, start_faces)) in vertices.iter().enumerate() {
+            for (end, end_faces) in &vertices[index + 1..] {
+                let shared = start_faces
+                    .iter()
+                    .filter(|face| end_faces.contains(face))
+                    .count();
+                if shared >= 2 {
+                    edges.push((*start, *end));
+                }
+            }
+        }
+        edges
+    }
+
+    /// The corners of `face`, in order around it, turning anticlockwise
+    /// when seen from outside the cell.
+    pub fn face_polygon(&self, face: usize) -> Vec<Vec3> {
+        let normal = self.faces[face].plane.normal;
+        let mut corners: Vec<Vec3> = self
+            .vertices()
+            .into_iter()
+            .filter(|(_, faces)| faces.contains(&face))
+            .map(|(point, _)| point)
+            .collect();
+        if corners.is_empty() {
+            return corners;
+        }
+        let center = corners
+            .iter()
+            .fold(Vec3::zero(), |sum, &corner| sum + corner)
+            / corners.len() as f32;
+        let first = corners[0] - center;
+        let angle = |corner: &Vec3| {
+            let offset = *corner - center;
+            normal.dot(&first.cross(&offset)).atan2(first.dot(&offset))
+        };
+        corners.sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap());
+        corners
+    }
+}
+
+/// A space built from convex cells whose faces are glued together by
+/// isometries. This is what rays are traversed through when rendering.
+#[derive(Clone, Debug, Default)]
+pub struct CellComplex {
+    pub cells: Vec<Cell>,
+    pub geometry: Geometry,
+}
+
+impl CellComplex {
+    pub fn new(geometry: Geometry, cells: Vec<Cell>) -> CellComplex {
+        CellComplex { cells, geometry }
+    }
+
+    pub fn size(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Move a ray which reached `face` of `cell` at parameter `t` across the
+    /// gluing, flipping its handedness if the gluing reverses orientation.
+    /// A mirror face reflects the ray back into `cell` instead. Returns
+    /// `None` if the face is not glued to anything.
+    pub fn cross(&self, cell: usize, face: usize, ray: &Ray, t: f32) -> Option<(FaceGluing, Ray)> {
+        let gluing = self.cells[cell].faces[face].gluing?;
+        let at_face = Ray {
+            origin: ray.point_at_parameter(t),
+            mirrored: ray.mirrored != gluing.reverses_orientation(),
+            ..*ray
+        };
+        Some((gluing, gluing.isometry.transform_ray(&at_face)))
+    }
+
+    /// Number the face pairings, giving a face and the face it is glued to
+    /// the same number. Faces which are not glued get one of their own.
+    pub fn gluing_classes(&self) -> Vec<Vec<usize>> {
+        let mut classes: Vec<Vec<Option<usize>>> = self
+            .cells
+            .iter()
+            .map(|cell| vec![None; cell.faces.len()])
+            .collect();
+        let mut count = 0;
+        for (index, cell) in self.cells.iter().enumerate() {
+            for (face, cell_face) in cell.faces.iter().enumerate() {
+                if classes[index][face].is_some() {
+                    continue;
+                }
+                classes[index][face] = Some(count);
+                if let Some(gluing) = cell_face.gluing {
+                    classes[gluing.cell][gluing.face] = Some(count);
+                }
+                count += 1;
+            }
+        }
+        classes
+            .into_iter()
+            .map(|faces| faces.into_iter().flatten().collect())
+            .collect()
+    }
+}
+
+impl From<&Triangulation> for CellComplex {
+    fn from(triangulation: &Triangulation) -> CellComplex {
+        let mut cells = Vec::with_capacity(triangulation.size());
+        for (index, tetrahedron) in triangulation.tetrahedra.iter().enumerate() {
+            let faces = (0..4)
+                .map(|face| CellFace {
+                    plane: tetrahedron.face_plane(face),
+                    gluing: tetrahedron.gluings[face].map(|gluing| FaceGluing {
+                        cell: gluing.tetrahedron,
+                        face: gluing.face,
+                        isometry: triangulation
+                            .gluing_isometry(index, face)
+                            .expect("Tetrahedron glued along a degenerate face!"),
+                    }),
+                })
+                .collect();
+            cells.push(Cell { faces });
+        }
+        CellComplex::new(triangulation.geometry, cells)
+    }
+}
+
+impl From<Triangulation> for CellComplex {
+    fn from(triangulation: Triangulation) -> CellComplex {
+        CellComplex::from(&triangulation)
+    }
+}
@@ -0,0 +1,158 @@
+use crate::math::geometry::horosphere::HorosphereGeometry;
+use crate::math::manifold::triangulation::{Tetrahedron, Triangulation};
+use crate::math::space::Geometry;
+use crate::math::vectors::Vec4;
+
+/// A horoball about one ideal vertex of one tetrahedron.
+#[derive(Clone, Copy, Debug)]
+pub struct CuspCorner {
+    /// Which cusp of the manifold the vertex is part of.
+    pub cusp: usize,
+    /// The light-like vector `N` of the horoball `-<X, N> <= 1`, in the
+    /// tetrahedron's chart.
+    pub pole: Vec4,
+}
+
+/// Neighbourhoods of the cusps of a hyperbolic triangulation, as a horoball
+/// about every ideal vertex of every tetrahedron. The horoballs about the
+/// vertices making up a cusp are the images of each other under the gluings,
+/// so together they are a single neighbourhood of the cusp whose boundary is
+/// a flat torus (or Klein bottle).
+#[derive(Clone, Debug)]
+pub struct CuspNeighborhoods {
+    /// The horoball at each vertex of each tetrahedron, or `None` for
+    /// vertices which are not ideal.
+    pub corners: Vec<[Option<CuspCorner>; 4]>,
+    pub cusps: usize,
+}
+
+impl CuspNeighborhoods {
+    /// The maximal cusp neighbourhoods: each cusp is grown until it touches
+    /// itself, then all of them are shrunk by the same distance until no two
+    /// overlap. Horoballs are only compared along the edges of the
+    /// triangulation, which finds where they first touch for the canonical
+    /// triangulations SnapPea produces, but may not for others.
+    pub fn maximal(triangulation: &Triangulation) -> CuspNeighborhoods {
+        assert_eq!(
+            triangulation.geometry,
+            Geometry::Hyperbolic,
+            "Only hyperbolic triangulations have cusps!"
+        );
+        let mut neighborhoods = CuspNeighborhoods::unscaled(triangulation);
+
+        // Two horoballs are ln(-<N, M> / 2) apart, so scaling the cusps so
+        // that this is 0 along the closest edge makes them touch there.
+        let gaps = neighborhoods.edge_gaps();
+        let mut scales = vec![f32::MAX; neighborhoods.cusps];
+        for &(first, second, gap) in &gaps {
+            if first == second {
+                scales[first] = scales[first].min(gap);
+            }
+        }
+        let scales: Vec<f32> = scales
+            .iter()
+            .map(|&gap| {
+                if gap == f32::MAX {
+                    1.0
+                } else {
+                    gap.sqrt().recip()
+                }
+            })
+            .collect();
+        let closest = gaps
+            .iter()
+            .map(|&(first, second, gap)| gap * scales[first] * scales[second])
+            .fold(f32::MAX, f32::min);
+        let shrink = if closest < 1.0 {
+            closest.sqrt().recip()
+        } else {
+            1.0
+        };
+        for corner in neighborhoods.corners.iter_mut().flatten().flatten() {
+            corner.pole *= scales[corner.cusp] * shrink;
+        }
+        neighborhoods
+    }
+
+    /// Push every horosphere `depth` further into its cusp, making the
+    /// neighbourhoods smaller (or larger, for negative depths).
+    pub fn shrink(mut self, depth: f32) -> CuspNeighborhoods {
+        for corner in self.corners.iter_mut().flatten().flatten() {
+            corner.pole *= depth.exp();
+        }
+        self
+    }
+
+    /// The horospheres bounding the neighbourhoods inside `tetrahedron`,
+    /// along with the cusp each belongs to.
+    pub fn horospheres(&self, tetrahedron: usize) -> Vec<(usize, HorosphereGeometry)> {
+        self.corners[tetrahedron]
+            .iter()
+            .flatten()
+            .map(|corner| (corner.cusp, HorosphereGeometry::from_pole(corner.pole)))
+            .collect()
+    }
+
+    /// Put a horoball on every ideal vertex, carried from tetrahedron to
+    /// tetrahedron through the gluings so that they match up, starting from
+    /// an arbitrary size for each cusp.
+    fn unscaled(triangulation: &Triangulation) -> CuspNeighborhoods {
+        let mut corners = vec![[None; 4]; triangulation.size()];
+        let mut cusps = 0;
+        for tetrahedron in 0..triangulation.size() {
+            for vertex in 0..4 {
+                let point = triangulation.tetrahedra[tetrahedron].vertices[vertex];
+                if corners[tetrahedron][vertex].is_some() || point.length_sq() < 1.0 - 1e-4 {
+                    continue;
+                }
+                corners[tetrahedron][vertex] = Some(CuspCorner {
+                    cusp: cusps,
+                    pole: Vec4::point(point),
+                });
+                let mut stack = vec![(tetrahedron, vertex)];
+                while let Some((current, vertex)) = stack.pop() {
+                    let corner = corners[current][vertex].unwrap();
+                    for face in 0..4 {
+                        if face == vertex {
+                            continue;
+                        }
+                        let gluing = match triangulation.tetrahedra[current].gluings[face] {
+                            Some(gluing) => gluing,
+                            None => continue,
+                        };
+                        let target = (gluing.tetrahedron, gluing.permutation[vertex]);
+                        if corners[target.0][target.1].is_some() {
+                            continue;
+                        }
+                        let isometry = triangulation
+                            .gluing_isometry(current, face)
+                            .expect("Tetrahedron glued along a degenerate face!");
+                        corners[target.0][target.1] = Some(CuspCorner {
+                            pole: isometry * corner.pole,
+                            ..corner
+                        });
+                        stack.push(target);
+                    }
+                }
+                cusps += 1;
+            }
+        }
+        CuspNeighborhoods { corners, cusps }
+    }
+
+    /// For every edge of every tetrahedron joining two ideal vertices, the
+    /// cusps at either end and `-<N, M> / 2` for their horoballs.
+    fn edge_gaps(&self) -> Vec<(usize, usize, f32)> {
+        let geometry = Geometry::Hyperbolic;
+        let mut gaps = Vec::new();
+        for corners in &self.corners {
+            for &(a, b) in Tetrahedron::EDGES.iter() {
+                if let (Some(first), Some(second)) = (corners[a], corners[b]) {
+                    let gap = -0.5 * geometry.inner(&first.pole, &second.pole);
+                    gaps.push((first.cusp, second.cusp, gap));
+                }
+            }
+        }
+        gaps
+    }
+}
@@ -0,0 +1,192 @@
+use std::io::{self, Write};
+
+use crate::math::manifold::complex::CellComplex;
+use crate::math::matrix::Mat4;
+use crate::math::space::Geometry;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// One copy of a cell laid out in the universal cover.
+#[derive(Clone, Debug)]
+pub struct CellCopy {
+    pub cell: usize,
+    /// Takes the cell's own chart to where the copy sits in the chart of the
+    /// development.
+    pub placement: Mat4,
+    /// How many gluings were crossed to reach the copy.
+    pub layer: usize,
+}
+
+/// The cells of a manifold unrolled into the universal cover, as seen from
+/// one cell: that cell in its own chart, surrounded by every copy of the
+/// cells reachable through at most `layers` gluings. This is the picture
+/// rays crossing the gluings draw, laid out explicitly.
+#[derive(Clone, Debug)]
+pub struct Development {
+    pub geometry: Geometry,
+    pub copies: Vec<CellCopy>,
+}
+
+impl Development {
+    /// Unroll `manifold` around `cell`, whose chart becomes the chart of the
+    /// development.
+    pub fn new(manifold: &CellComplex, cell: usize, layers: usize) -> Development {
+        let mut copies = vec![CellCopy {
+            cell,
+            placement: Mat4::identity(),
+            layer: 0,
+        }];
+        let mut frontier = 0;
+        while frontier < copies.len() {
+            let copy = copies[frontier].clone();
+            frontier += 1;
+            if copy.layer >= layers {
+                continue;
+            }
+            for face in &manifold.cells[copy.cell].faces {
+                let gluing = match face.gluing {
+                    Some(gluing) => gluing,
+                    None => continue,
+                };
+                // The gluing takes this cell's chart to its neighbour's, so
+                // its inverse puts the neighbour next to this copy.
+                let inverse = gluing.isometry.inverse().expect("Gluings are invertible!");
+                let placement = copy.placement.compose(&inverse);
+                // Different paths reach the same copy with placements which
+                // differ by rounding error.
+                let seen = copies
+                    .iter()
+                    .any(|other| other.cell == gluing.cell && other.placement.same_map(&placement));
+                if seen {
+                    continue;
+                }
+                copies.push(CellCopy {
+                    cell: gluing.cell,
+                    placement,
+                    layer: copy.layer + 1,
+                });
+            }
+        }
+        Development {
+            geometry: manifold.geometry,
+            copies,
+        }
+    }
+
+    /// Carry a point of a copy's cell to the chart of the development. In
+    /// spherical space copies can land outside the chart's hemisphere, where
+    /// this gives `None`.
+    pub fn place(&self, copy: &CellCopy, point: Vec3) -> Option<Vec3> {
+        let placed = copy.placement * Vec4::point(point);
+        if self.geometry == Geometry::Spherical && placed.w <= 1e-6 {
+            return None;
+        }
+        Some(placed.dehomogenize())
+    }
+
+    /// The faces of every copy as polygons in the chart of the development,
+    /// along with the copy they belong to. Faces which do not fit in the
+    /// chart are left out.
+    pub fn faces(&self, manifold: &CellComplex) -> Vec<(usize, Vec<Vec3>)> {
+        let mut faces = Vec::new();
+        for (index, copy) in self.copies.iter().enumerate() {
+            let cell = &manifold.cells[copy.cell];
+            for face in 0..cell.faces.len() {
+                let polygon: Option<Vec<Vec3>> = cell
+                    .face_polygon(face)
+                    .into_iter()
+                    .map(|corner| self.place(copy, corner))
+                    .collect();
+                match polygon {
+                    Some(polygon) if polygon.len() >= 3 => faces.push((index, polygon)),
+                    _ => {}
+                }
+            }
+        }
+        faces
+    }
+
+    /// The edges of every copy in the chart of the development.
+    pub fn edges(&self, manifold: &CellComplex) -> Vec<(usize, Vec3, Vec3)> {
+        let mut edges = Vec::new();
+        for (index, copy) in self.copies.iter().enumerate() {
+            for (start, end) in manifold.cells[copy.cell].edges() {
+                if let (Some(start), Some(end)) = (self.place(copy, start), self.place(copy, end)) {
+                    edges.push((index, start, end));
+                }
+            }
+        }
+        edges
+    }
+
+    /// Write the faces of the development as a Wavefront OBJ, with a group
+    /// for each copy named after its cell and layer. Coordinates are those
+    /// of the chart, so hyperbolic developments come out in the Klein model.
+    pub fn write_obj<W: Write>(&self, manifold: &CellComplex, out: &mut W) -> io::Result<()> {
+        let mut written = 0;
+        let mut group = None;
+        for (index, polygon) in self.faces(manifold) {
+            if group != Some(index) {
+                let copy = &self.copies[index];
+                writeln!(out, "g copy{}_cell{}_layer{}", index, copy.cell, copy.layer)?;
+                group = Some(index);
+            }
+            for corner in &polygon {
+                writeln!(out, "v {} {} {}", corner.x, corner.y, corner.z)?;
+            }
+            let indices: Vec<String> = (written + 1..=written + polygon.len())
+                .map(|index| index.to_string())
+                .collect();
+            writeln!(out, "f {}", indices.join(" "))?;
+            written += polygon.len();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::regina;
+    use crate::math::complex::Complex;
+    use crate::math::manifold::ideal::IdealTriangulation;
+
+    fn figure_eight() -> CellComplex {
+        let combinatorics = regina::from_isosig("cPcbbbiht").unwrap();
+        let regular = Complex::from_polar(1.0, std::f32::consts::FRAC_PI_3);
+        CellComplex::from(IdealTriangulation::new(&combinatorics, vec![regular; 2]).triangulation)
+    }
+
+    #[test]
+    fn figure_eight_copies_are_distinct() {
+        let manifold = figure_eight();
+        for layers in 1..=4 {
+            let development = Development::new(&manifold, 0, layers);
+            // A cell in the middle of each copy, to compare where they sit.
+            let centers: Vec<(usize, Vec4)> = development
+                .copies
+                .iter()
+                .map(|copy| {
+                    let corners = manifold.cells[copy.cell].vertices();
+                    let center = corners
+                        .iter()
+                        .fold(Vec3::zero(), |sum, (corner, _)| sum + *corner)
+                        / corners.len() as f32;
+                    (copy.cell, copy.placement * Vec4::point(center))
+                })
+                .collect();
+            for (index, (cell, center)) in centers.iter().enumerate() {
+                for (other_cell, other) in &centers[index + 1..] {
+                    let distance = manifold
+                        .geometry
+                        .distance(center.dehomogenize(), other.dehomogenize());
+                    assert!(
+                        cell != other_cell || distance > 1e-2,
+                        "cell {} is placed twice with {} layers",
+                        cell,
+                        layers
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use crate::math::geometry::plane::Plane;
+use crate::math::manifold::complex::{Cell, CellComplex, CellFace, FaceGluing};
+use crate::math::matrix::Mat4;
+use crate::math::quaternion::Quaternion;
+use crate::math::space::Geometry;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// A face of a Dirichlet domain: the part of the plane halfway between the
+/// basepoint and its image under `element` which is nearer the basepoint
+/// than any other image.
+#[derive(Clone, Debug)]
+pub struct DirichletFace {
+    pub plane: Plane,
+    pub element: Mat4,
+    /// The face halfway to the image under the inverse of `element`, which
+    /// `element` carries onto this one.
+    pub partner: Option<usize>,
+    /// The corners of the face, in order around it.
+    pub polygon: Vec<Vec3>,
+}
+
+/// The Dirichlet domain of a group of isometries about a basepoint: every
+/// point of the chart which is at least as close to the basepoint as to any
+/// of its images under the group. Gluing its faces in pairs by the group
+/// gives back the quotient of space by the group.
+///
+/// Only the group elements which can be written as words of length at most
+/// `word_length` in the generators are used, so if that is too short the
+/// domain comes out too large, and faces whose partners were never found are
+/// left unglued.
+#[derive(Clone, Debug)]
+pub struct DirichletDomain {
+    pub geometry: Geometry,
+    pub basepoint: Vec3,
+    pub faces: Vec<DirichletFace>,
+}
+
+fn close(a: &Vec4, b: &Vec4) -> bool {
+    (*a - *b).length() < 1e-3 * (1.0 + a.length())
+}
+
+/// Cut away the part of a convex polygon on the positive side of a plane.
+fn clip(polygon: &[Vec3], plane: &Plane) -> Vec<Vec3> {
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (index, &current) in polygon.iter().enumerate() {
+        let next = polygon[(index + 1) % polygon.len()];
+        let here = plane.signed_distance(current);
+        let there = plane.signed_distance(next);
+        if here <= 0.0 {
+            clipped.push(current);
+        }
+        if (here < 0.0 && there > 0.0) || (here > 0.0 && there < 0.0) {
+            clipped.push(current + (next - current) * (here / (here - there)));
+        }
+    }
+    clipped
+}
+
+/// A square in the plane, larger than a box of half width `reach`.
+fn face_square(plane: &Plane, reach: f32) -> Vec<Vec3> {
+    let center = plane.normal * plane.offset;
+    let helper = if plane.normal.x.abs() < 0.9 {
+        Vec3::right()
+    } else {
+        Vec3::up()
+    };
+    let u = plane.normal.cross(&helper).normalized() * (4.0 * reach);
+    let v = plane.normal.cross(&u);
+    vec![
+        center + u + v,
+        center - u + v,
+        center - u - v,
+        center + u - v,
+    ]
+}
+
+fn area(polygon: &[Vec3]) -> f32 {
+    let mut total = Vec3::zero();
+    for (index, corner) in polygon.iter().enumerate() {
+        total += corner.cross(&polygon[(index + 1) % polygon.len()]);
+    }
+    0.5 * total.length()
+}
+
+/// The rotation of the 3-sphere which multiplies on the left by a unit
+/// quaternion, taking the chart's `w` as the real part.
+fn left_multiplication(q: Quaternion) -> Mat4 {
+    Mat4::from_rows([
+        [q.a, -q.d, q.c, q.b],
+        [q.d, q.a, -q.b, q.c],
+        [-q.c, q.b, q.a, q.d],
+        [-q.b, -q.c, -q.d, q.a],
+    ])
+}
+
+impl DirichletDomain {
+    /// The Poincare homology sphere: the 3-sphere, as the unit quaternions,
+    /// divided by the 120 element binary icosahedral group acting on the
+    /// left. Its Dirichlet domain about the origin is a regular spherical
+    /// dodecahedron whose opposite faces are glued with a tenth of a turn.
+    pub fn poincare_sphere() -> DirichletDomain {
+        let golden = 0.5 * (1.0 + 5.0f32.sqrt());
+        let generators = [
+            left_multiplication(Quaternion::new(golden, 1.0 / golden, 1.0, 0.0) * 0.5),
+            left_multiplication(Quaternion::new(1.0, 1.0, 1.0, 1.0) * 0.5),
+        ];
+        DirichletDomain::new(Geometry::Spherical, Vec3::zero(), &generators, 12)
+    }
+
+    /// Build the domain about `basepoint` for the group generated by
+    /// `generators`, given as isometries of the model of `geometry` (affine
+    /// maps for Euclidean space, Lorentz matrices for hyperbolic space and
+    /// rotations of R^4 for the 3-sphere). The basepoint must not be fixed
+    /// by anything in the group but the identity.
+    pub fn new(
+        geometry: Geometry,
+        basepoint: Vec3,
+        generators: &[Mat4],
+        word_length: usize,
+    ) -> DirichletDomain {
+        let base = geometry.lift(basepoint);
+        let mut letters = Vec::with_capacity(2 * generators.len());
+        for generator in generators {
+            letters.push(*generator);
+            letters.push(generator.inverse().expect("Generators must be invertible!"));
+        }
+
+        // Walk out through the group, keeping one element per image of the
+        // basepoint.
+        let mut elements: Vec<(Mat4, Vec4)> = Vec::new();
+        let mut frontier = vec![Mat4::identity()];
+        for _ in 0..word_length {
+            let mut next = Vec::new();
+            for element in &frontier {
+                for letter in &letters {
+                    let product = element.compose(letter);
+                    let image = product * base;
+                    if close(&image, &base) {
+                        continue;
+                    }
+                    if elements.iter().any(|(_, seen)| close(&image, seen)) {
+                        continue;
+                    }
+                    elements.push((product, image));
+                    next.push(product);
+                }
+            }
+            frontier = next;
+        }
+
+        // The bisecting planes, facing away from the basepoint. The plane
+        // between a point of the 3-sphere and its antipode through the
+        // origin is the chart's plane at infinity, which bounds it anyway.
+        let planes: Vec<Option<Plane>> = elements
+            .iter()
+            .map(|(_, image)| {
+                let (normal, offset) = match geometry {
+                    Geometry::Euclidean => {
+                        let image = image.dehomogenize();
+                        (
+                            image - basepoint,
+                            0.5 * (image.length_sq() - basepoint.length_sq()),
+                        )
+                    }
+                    // Nearer the basepoint is where <X, image - base> < 0.
+                    Geometry::Hyperbolic => {
+                        let difference = *image - base;
+                        (difference.xyz(), difference.w)
+                    }
+                    Geometry::Spherical => {
+                        let difference = *image - base;
+                        (difference.xyz(), -difference.w)
+                    }
+                };
+                let length = normal.length();
+                if length < 1e-6 {
+                    return None;
+                }
+                Some(Plane::new(normal / length, offset / length))
+            })
+            .collect();
+
+        // Clip each plane against all the others, and against a box big
+        // enough to hold the domain, to find which of them are really faces.
+        let reach = match geometry {
+            Geometry::Hyperbolic => 1.0,
+            Geometry::Euclidean => {
+                1.0 + 2.0
+                    * elements
+                        .iter()
+                        .map(|(_, image)| image.dehomogenize().length())
+                        .fold(basepoint.length(), f32::max)
+            }
+            Geometry::Spherical => 1e2,
+        };
+        let mut walls: Vec<Plane> = Vec::new();
+        for axis in [Vec3::right(), Vec3::up(), Vec3::forward()].iter() {
+            walls.push(Plane::new(*axis, reach));
+            walls.push(Plane::new(-*axis, reach));
+        }
+        let mut faces: Vec<DirichletFace> = Vec::new();
+        let mut kept = Vec::new();
+        for (index, plane) in planes.iter().enumerate() {
+            let plane = match plane {
+                Some(plane) => plane,
+                None => continue,
+            };
+            let cuts = planes
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .filter_map(|(_, cut)| cut.as_ref())
+                .chain(walls.iter());
+            // Planes which only touch the domain along an edge or at a
+            // corner vanish once everything else is pulled in a little.
+            let margin = 1e-4 * reach;
+            let interior = cuts
+                .clone()
+                .fold(face_square(plane, reach), |polygon, cut| {
+                    clip(&polygon, &Plane::new(cut.normal, cut.offset - margin))
+                });
+            if interior.len() < 3 || area(&interior) <= 0.0 {
+                continue;
+            }
+            let mut polygon = cuts.fold(face_square(plane, reach), |polygon, cut| {
+                clip(&polygon, cut)
+            });
+            // Planes through a corner leave repeated corners behind.
+            polygon.dedup_by(|a, b| (*a - *b).length() < margin);
+            while polygon.len() > 1 && (polygon[0] - polygon[polygon.len() - 1]).length() < margin {
+                polygon.pop();
+            }
+            kept.push(index);
+            faces.push(DirichletFace {
+                plane: *plane,
+                element: elements[index].0,
+                partner: None,
+                polygon,
+            });
+        }
+
+        // A face and its partner bisect the basepoint and its images under
+        // an element and that element's inverse.
+        for face in faces.iter_mut() {
+            let inverse = face
+                .element
+                .inverse()
+                .expect("Group elements are invertible!");
+            let image = inverse * base;
+            face.partner = kept
+                .iter()
+                .position(|&index| close(&elements[index].1, &image));
+        }
+
+        DirichletDomain {
+            geometry,
+            basepoint,
+            faces,
+        }
+    }
+}
+
+impl From<&DirichletDomain> for CellComplex {
+    fn from(domain: &DirichletDomain) -> CellComplex {
+        // Leaving through a face enters the copy of the domain moved by the
+        // face's element, which is brought back by the inverse.
+        let faces = domain
+            .faces
+            .iter()
+            .map(|face| CellFace {
+                plane: face.plane,
+                gluing: face.partner.map(|partner| FaceGluing {
+                    cell: 0,
+                    face: partner,
+                    isometry: face
+                        .element
+                        .inverse()
+                        .expect("Group elements are invertible!"),
+                }),
+            })
+            .collect();
+        CellComplex::new(domain.geometry, vec![Cell { faces }])
+    }
+}
+
+impl From<DirichletDomain> for CellComplex {
+    fn from(domain: DirichletDomain) -> CellComplex {
+        CellComplex::from(&domain)
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::math::manifold::complex::FaceGluing;
+use crate::math::matrix::Mat4;
+
+/// A face pairing, named by the cell and face a ray leaves through. Leaving
+/// through the face on the other side of the pairing gives its inverse.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Generator {
+    pub cell: usize,
+    pub face: usize,
+}
+
+/// The face pairings a ray has passed through on its way from the camera,
+/// which says which copy of the fundamental domain it ended up seeing.
+#[derive(Clone, Debug)]
+pub struct Holonomy {
+    /// How many gluings were crossed in total.
+    pub crossings: u32,
+    /// The generators crossed, first to last, with any generator followed
+    /// directly by its inverse cancelled out. Relations between generators
+    /// are not applied, so two different words can name the same copy.
+    pub word: Vec<Generator>,
+    /// The gluing isometries composed along the way. This takes the local
+    /// coordinates the ray started in to those it ended up in, and unlike
+    /// the word it is the same for every path to the same copy.
+    pub isometry: Mat4,
+}
+
+impl Holonomy {
+    pub fn new() -> Holonomy {
+        Holonomy {
+            crossings: 0,
+            word: Vec::new(),
+            isometry: Mat4::identity(),
+        }
+    }
+
+    /// Record leaving `cell` through `face`, which is glued by `gluing`.
+    pub fn cross(&mut self, cell: usize, face: usize, gluing: &FaceGluing) {
+        self.crossings += 1;
+        self.isometry = gluing.isometry.compose(&self.isometry);
+        let inverse = Generator {
+            cell: gluing.cell,
+            face: gluing.face,
+        };
+        if self.word.last() == Some(&inverse) {
+            self.word.pop();
+        } else {
+            self.word.push(Generator { cell, face });
+        }
+    }
+
+    /// The number of generators left after cancelling.
+    pub fn length(&self) -> usize {
+        self.word.len()
+    }
+
+    /// A number for picking colors, which is the same for every path to the
+    /// same copy of the fundamental domain unless rounding error carries an
+    /// entry across a multiple of 1/64. Use `same_copy` to compare copies.
+    pub fn key(&self) -> u64 {
+        // A matrix and its negative are the same map of the chart.
+        let sign = self.isometry.sign();
+        let mut hasher = DefaultHasher::new();
+        for &entry in self.isometry.rows.iter().flatten() {
+            // Rounding away the error picked up along the way.
+            ((entry * sign * 64.0).round() as i64).hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Whether the two paths lead to the same copy of the fundamental
+    /// domain.
+    pub fn same_copy(&self, other: &Holonomy) -> bool {
+        self.isometry.same_map(&other.isometry)
+    }
+}
+
+impl Default for Holonomy {
+    fn default() -> Holonomy {
+        Holonomy::new()
+    }
+}
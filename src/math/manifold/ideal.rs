@@ -0,0 +1,104 @@
+use crate::math::complex::Complex;
+use crate::math::manifold::complex::CellComplex;
+use crate::math::manifold::triangulation::{Tetrahedron, Triangulation};
+use crate::math::space::Geometry;
+use crate::math::vectors::Vec3;
+
+/// A triangulation by ideal hyperbolic tetrahedra, each described by its
+/// complex shape parameter. Tetrahedron `i` is placed with its vertices at
+/// `infinity, 0, 1, shapes[i]` on the boundary of upper half space, which
+/// gives the edges 0-1 and 2-3 the parameter `z`, the edges 0-2 and 1-3 the
+/// parameter `1 / (1 - z)` and the edges 0-3 and 1-2 the parameter
+/// `1 - 1 / z`. This is the same convention SnapPea uses.
+#[derive(Clone, Debug)]
+pub struct IdealTriangulation {
+    pub triangulation: Triangulation,
+    pub shapes: Vec<Complex>,
+}
+
+/// The point of the sphere at infinity (the unit sphere of the Klein model)
+/// corresponding to a point on the boundary of upper half space.
+fn boundary_point(point: Option<Complex>) -> Vec3 {
+    match point {
+        None => Vec3::forward(),
+        Some(point) => {
+            let norm_sq = point.norm_sq();
+            Vec3::new(2.0 * point.re, 2.0 * point.im, norm_sq - 1.0) / (norm_sq + 1.0)
+        }
+    }
+}
+
+impl IdealTriangulation {
+    /// Take the gluings of `combinatorics` and give every tetrahedron the
+    /// ideal shape in `shapes`.
+    pub fn new(combinatorics: &Triangulation, shapes: Vec<Complex>) -> IdealTriangulation {
+        assert_eq!(
+            combinatorics.size(),
+            shapes.len(),
+            "Need exactly one shape per tetrahedron!"
+        );
+        let mut triangulation = Triangulation {
+            geometry: Geometry::Hyperbolic,
+            ..combinatorics.clone()
+        };
+        for (tetrahedron, &shape) in triangulation.tetrahedra.iter_mut().zip(shapes.iter()) {
+            tetrahedron.vertices = [
+                boundary_point(None),
+                boundary_point(Some(Complex::zero())),
+                boundary_point(Some(Complex::one())),
+                boundary_point(Some(shape)),
+            ];
+        }
+        IdealTriangulation {
+            triangulation,
+            shapes,
+        }
+    }
+
+    /// The shape parameter attached to the edge between vertices `a` and
+    /// `b` of a tetrahedron.
+    pub fn edge_parameter(&self, tetrahedron: usize, a: usize, b: usize) -> Complex {
+        let z = self.shapes[tetrahedron];
+        match Tetrahedron::edge_index(a, b) {
+            0 | 5 => z,
+            1 | 4 => (Complex::one() - z).inv(),
+            _ => Complex::one() - z.inv(),
+        }
+    }
+
+    /// How far each edge equation is from holding. Going around an edge the
+    /// logarithms of the edge parameters must add up to `2 pi i`, which says
+    /// both that the tetrahedra close up around the edge and that they do so
+    /// without shearing along it.
+    pub fn gluing_equation_errors(&self) -> Vec<f32> {
+        self.triangulation
+            .edge_classes()
+            .iter()
+            .map(|class| {
+                let mut total = Complex::zero();
+                for &(tetrahedron, a, b) in class {
+                    total += self.edge_parameter(tetrahedron, a, b).ln();
+                }
+                (total - 2.0 * std::f32::consts::PI * Complex::i()).norm()
+            })
+            .collect()
+    }
+
+    pub fn satisfies_gluing_equations(&self, tolerance: f32) -> bool {
+        self.gluing_equation_errors()
+            .iter()
+            .all(|&error| error <= tolerance)
+    }
+}
+
+impl From<&IdealTriangulation> for CellComplex {
+    fn from(ideal: &IdealTriangulation) -> CellComplex {
+        CellComplex::from(&ideal.triangulation)
+    }
+}
+
+impl From<IdealTriangulation> for CellComplex {
+    fn from(ideal: IdealTriangulation) -> CellComplex {
+        CellComplex::from(&ideal.triangulation)
+    }
+}
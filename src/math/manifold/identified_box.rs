@@ -0,0 +1,165 @@
+use crate::math::geometry::plane::Plane;
+use crate::math::manifold::complex::{Cell, CellComplex, CellFace, FaceGluing};
+use crate::math::matrix::Mat4;
+use crate::math::quaternion::Quaternion;
+use crate::math::space::Geometry;
+use crate::math::vectors::Vec3;
+
+/// The extra isometry applied when a ray passes from the positive face of an
+/// axis to the negative face, on top of the translation across the box.
+#[derive(Clone, Copy, Debug)]
+pub enum Twist {
+    Translation,
+    /// Turn by this many radians about the axis joining the two faces.
+    Rotation(f32),
+    /// Mirror across the plane with this normal. The normal must be
+    /// perpendicular to the axis joining the two faces.
+    Reflection(Vec3),
+    /// Leave the two faces unglued from each other and make each of them a
+    /// mirror instead, so the box becomes the fundamental domain of an
+    /// orbifold.
+    Mirror,
+}
+
+/// A box centered on the origin whose opposite faces are identified. With
+/// every axis glued by a translation this is the 3-torus; twisting the
+/// gluings gives the other closed Euclidean manifolds which have a box as a
+/// fundamental domain (half-turn space, quarter-turn space, Klein bottle
+/// times a circle, ...). Axes without a gluing are left as open walls.
+#[derive(Clone, Copy, Debug)]
+pub struct IdentifiedBox {
+    pub extents: Vec3,
+    pub gluings: [Option<Twist>; 3],
+}
+
+fn axis(index: usize) -> Vec3 {
+    match index {
+        0 => Vec3::right(),
+        1 => Vec3::up(),
+        2 => Vec3::forward(),
+        _ => panic!("Invalid axis for box!"),
+    }
+}
+
+impl IdentifiedBox {
+    pub fn new(extents: Vec3) -> IdentifiedBox {
+        IdentifiedBox {
+            extents,
+            gluings: [None; 3],
+        }
+    }
+
+    pub fn three_torus(extents: Vec3) -> IdentifiedBox {
+        IdentifiedBox::new(extents)
+            .glue(0, Twist::Translation)
+            .glue(1, Twist::Translation)
+            .glue(2, Twist::Translation)
+    }
+
+    /// The 3-torus, except that passing through the z faces turns you by
+    /// half a revolution.
+    pub fn half_turn_space(extents: Vec3) -> IdentifiedBox {
+        IdentifiedBox::three_torus(extents).glue(2, Twist::Rotation(std::f32::consts::PI))
+    }
+
+    /// Requires the box to have a square cross-section perpendicular to z.
+    pub fn quarter_turn_space(extents: Vec3) -> IdentifiedBox {
+        IdentifiedBox::three_torus(extents).glue(2, Twist::Rotation(std::f32::consts::FRAC_PI_2))
+    }
+
+    /// The non-orientable product of a Klein bottle with a circle. Passing
+    /// through the z faces mirrors you left to right.
+    pub fn klein_bottle_cross_circle(extents: Vec3) -> IdentifiedBox {
+        IdentifiedBox::three_torus(extents).glue(2, Twist::Reflection(Vec3::right()))
+    }
+
+    /// The orbifold with every face of the box a mirror: standing inside it
+    /// is like standing in a kaleidoscope.
+    pub fn kaleidoscope(extents: Vec3) -> IdentifiedBox {
+        IdentifiedBox::new(extents)
+            .glue(0, Twist::Mirror)
+            .glue(1, Twist::Mirror)
+            .glue(2, Twist::Mirror)
+    }
+
+    pub fn glue(mut self, axis: usize, twist: Twist) -> IdentifiedBox {
+        self.gluings[axis] = Some(twist);
+        self
+    }
+
+    /// The isometry taking the positive face along `index` onto the negative
+    /// face. There is none if the faces are mirrors.
+    pub fn gluing_isometry(&self, index: usize) -> Option<Mat4> {
+        let twist = self.gluings[index]?;
+        let direction = axis(index);
+        let linear = match twist {
+            Twist::Mirror => return None,
+            Twist::Translation => Mat4::identity(),
+            Twist::Rotation(angle) => Mat4::rotation(Quaternion::axis_angle(direction, angle)),
+            Twist::Reflection(normal) => {
+                assert!(
+                    normal.dot(&direction).abs() < 0.0001,
+                    "Box reflections must fix the axis they glue along!"
+                );
+                Mat4::reflection(normal)
+            }
+        };
+        // The twist has to carry the face onto itself, otherwise the gluing
+        // would not match up points on the boundary of the box.
+        let corner = linear.transform_point(self.extents).abs();
+        assert!(
+            (corner - self.extents).abs().length() < 0.001 * self.extents.length(),
+            "Twist {:?} does not map the box face onto itself!",
+            twist
+        );
+        Some(Mat4::translation(-2.0 * self.extents[index] * direction) * linear)
+    }
+}
+
+impl From<&IdentifiedBox> for CellComplex {
+    fn from(identified: &IdentifiedBox) -> CellComplex {
+        // Faces come in pairs: positive x, negative x, positive y, ...
+        let mut faces = Vec::with_capacity(6);
+        for index in 0..3 {
+            let direction = axis(index);
+            let extent = identified.extents[index];
+            if let Some(Twist::Mirror) = identified.gluings[index] {
+                let sides = [(2 * index, direction), (2 * index + 1, -direction)];
+                for &(face, normal) in sides.iter() {
+                    let plane = Plane::new(normal, extent);
+                    faces.push(CellFace {
+                        plane,
+                        gluing: Some(FaceGluing::mirror(Geometry::Euclidean, 0, face, &plane)),
+                    });
+                }
+                continue;
+            }
+            let isometry = identified.gluing_isometry(index);
+            faces.push(CellFace {
+                plane: Plane::new(direction, extent),
+                gluing: isometry.map(|isometry| FaceGluing {
+                    cell: 0,
+                    face: 2 * index + 1,
+                    isometry,
+                }),
+            });
+            faces.push(CellFace {
+                plane: Plane::new(-direction, extent),
+                gluing: isometry.map(|isometry| FaceGluing {
+                    cell: 0,
+                    face: 2 * index,
+                    isometry: isometry
+                        .inverse()
+                        .expect("Box gluings are always invertible!"),
+                }),
+            });
+        }
+        CellComplex::new(Geometry::Euclidean, vec![Cell { faces }])
+    }
+}
+
+impl From<IdentifiedBox> for CellComplex {
+    fn from(identified: IdentifiedBox) -> CellComplex {
+        CellComplex::from(&identified)
+    }
+}
@@ -0,0 +1,233 @@
+use crate::math::geometry::tube::TubeGeometry;
+use crate::math::manifold::complex::CellComplex;
+use crate::math::manifold::holonomy::Holonomy;
+use crate::math::ray::Ray;
+use crate::math::space::Geometry;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// A geodesic loop based at a point of a manifold: the geodesic from the
+/// point to one of its images in the universal cover, which leaves the point
+/// in one direction and comes back to it from another. The shortest ones are
+/// twice the injectivity radius at the point long.
+#[derive(Clone, Debug)]
+pub struct GeodesicLoop {
+    pub length: f32,
+    /// The gluings the loop crosses. Going around the loop carries the
+    /// chart of its cell to itself by `holonomy.isometry`.
+    pub holonomy: Holonomy,
+    /// The direction the loop sets off in, in the chart of its cell.
+    pub direction: Vec3,
+}
+
+/// Finds geodesic loops by shooting rays out from a point in every direction
+/// and following them through the gluings. Every time a ray comes back into
+/// the cell it started in, the point's copy in that cell is an image of it in
+/// the universal cover, and the geodesic to that image is a loop. A ray does
+/// not need to pass through the image itself, so the loops found are exact,
+/// but copies of the cell which no ray reaches are missed.
+#[derive(Clone, Copy, Debug)]
+pub struct LoopSearch {
+    /// How many rays to shoot, spread evenly over the sphere of directions.
+    pub rays: usize,
+    /// How far rays are followed, and so the longest loop found.
+    pub max_length: f32,
+    pub max_crossings: u32,
+}
+
+/// Directions spread evenly over the unit sphere, on a Fibonacci spiral.
+fn sphere_directions(count: usize) -> Vec<Vec3> {
+    let turn = std::f32::consts::PI * (3.0 - 5.0f32.sqrt());
+    (0..count)
+        .map(|index| {
+            let height = 1.0 - (2.0 * index as f32 + 1.0) / count as f32;
+            let across = (1.0 - height * height).max(0.0).sqrt();
+            let angle = turn * index as f32;
+            Vec3::new(across * angle.cos(), height, across * angle.sin())
+        })
+        .collect()
+}
+
+/// The geodesic distance between two points of the model. Unlike measuring
+/// in a chart this works for points of the 3-sphere on either side of the
+/// chart's hemisphere.
+fn separation(geometry: Geometry, a: &Vec4, b: &Vec4) -> f32 {
+    match geometry {
+        Geometry::Euclidean => (a.dehomogenize() - b.dehomogenize()).length(),
+        Geometry::Hyperbolic => {
+            let norms = (geometry.inner(a, a) * geometry.inner(b, b)).sqrt();
+            (-geometry.inner(a, b) / norms).max(1.0).acosh()
+        }
+        Geometry::Spherical => {
+            let norms = a.length() * b.length();
+            (a.dot(b) / norms).clamp(-1.0, 1.0).acos()
+        }
+    }
+}
+
+/// The direction in the chart of the geodesic from `from` towards `to`,
+/// both points of the model.
+fn chart_direction(geometry: Geometry, from: &Vec4, to: &Vec4) -> Vec3 {
+    // The part of `to - from` tangent to the model at `from`, carried down
+    // to the chart by the derivative of dehomogenizing.
+    let tangent = match geometry {
+        Geometry::Euclidean => *to - *from,
+        _ => *to - *from * (geometry.inner(to, from) / geometry.inner(from, from)),
+    };
+    (tangent.xyz() * from.w - from.xyz() * tangent.w).normalized()
+}
+
+impl LoopSearch {
+    pub fn new() -> LoopSearch {
+        LoopSearch {
+            rays: 2000,
+            max_length: 10.0,
+            max_crossings: 64,
+        }
+    }
+
+    /// The geodesic loops based at `point` of `cell`, shortest first. A loop
+    /// and the same loop walked backwards are only listed once.
+    pub fn find(&self, manifold: &CellComplex, cell: usize, point: Vec3) -> Vec<GeodesicLoop> {
+        let geometry = manifold.geometry;
+        let base = geometry.lift(point);
+        let mut loops = Vec::new();
+        for direction in sphere_directions(self.rays) {
+            let mut ray = Ray::new(point, direction).in_geometry(geometry);
+            let mut current = cell;
+            let mut entered = None;
+            let mut holonomy = Holonomy::new();
+            let mut travelled = 0.0;
+            while holonomy.crossings < self.max_crossings {
+                let (t, face) = match manifold.cells[current].exit(&ray, entered) {
+                    Some(exit) => exit,
+                    None => break,
+                };
+                travelled += geometry.distance(ray.origin, ray.point_at_parameter(t));
+                if travelled > self.max_length {
+                    break;
+                }
+                let (gluing, next) = match manifold.cross(current, face, &ray, t) {
+                    Some(crossing) => crossing,
+                    None => break,
+                };
+                holonomy.cross(current, face, &gluing);
+                ray = next;
+                current = gluing.cell;
+                entered = Some(gluing.face);
+                if current != cell {
+                    continue;
+                }
+
+                // The point itself, seen from here, is where the holonomy
+                // takes its image in the universal cover.
+                let inverse = match holonomy.isometry.inverse() {
+                    Some(inverse) => inverse,
+                    None => break,
+                };
+                let image = inverse * base;
+                let length = separation(geometry, &base, &image);
+                if length < 1e-4 || length > self.max_length {
+                    continue;
+                }
+                // A loop run backwards is the same loop.
+                let seen = loops.iter().any(|found: &GeodesicLoop| {
+                    found.holonomy.isometry.same_map(&holonomy.isometry)
+                        || found.holonomy.isometry.same_map(&inverse)
+                });
+                if seen {
+                    continue;
+                }
+                loops.push(GeodesicLoop {
+                    length,
+                    holonomy: holonomy.clone(),
+                    direction: chart_direction(geometry, &base, &image),
+                });
+            }
+        }
+        loops.sort_by(|a, b| a.length.partial_cmp(&b.length).unwrap());
+        loops
+    }
+
+    /// The injectivity radius at `point` of `cell`: half the length of the
+    /// shortest geodesic loop there, or `None` if no loop was found.
+    pub fn injectivity_radius(
+        &self,
+        manifold: &CellComplex,
+        cell: usize,
+        point: Vec3,
+    ) -> Option<f32> {
+        self.find(manifold, cell, point)
+            .first()
+            .map(|shortest| 0.5 * shortest.length)
+    }
+}
+
+impl Default for LoopSearch {
+    fn default() -> LoopSearch {
+        LoopSearch::new()
+    }
+}
+
+impl GeodesicLoop {
+    /// Cut the loop based at `point` of `cell` into the segments lying in
+    /// each cell it passes through, as the cell and the ends of the segment
+    /// in that cell's chart.
+    pub fn pieces(
+        &self,
+        manifold: &CellComplex,
+        cell: usize,
+        point: Vec3,
+    ) -> Vec<(usize, Vec3, Vec3)> {
+        let geometry = manifold.geometry;
+        let mut ray = Ray::new(point, self.direction).in_geometry(geometry);
+        let mut current = cell;
+        let mut entered = None;
+        let mut travelled = 0.0;
+        let mut pieces = Vec::new();
+        // Rounding can add a crossing or two where the loop grazes an edge.
+        for _ in 0..=self.holonomy.crossings + 2 {
+            let remaining = self.length - travelled;
+            let (t, face) = match manifold.cells[current].exit(&ray, entered) {
+                Some(exit) => exit,
+                None => {
+                    let t = geometry.parameter_at_distance(&ray, remaining);
+                    pieces.push((current, ray.origin, ray.point_at_parameter(t)));
+                    break;
+                }
+            };
+            let end = ray.point_at_parameter(t);
+            let length = geometry.distance(ray.origin, end);
+            if length >= remaining {
+                let t = geometry.parameter_at_distance(&ray, remaining);
+                pieces.push((current, ray.origin, ray.point_at_parameter(t)));
+                break;
+            }
+            pieces.push((current, ray.origin, end));
+            travelled += length;
+            let (gluing, next) = match manifold.cross(current, face, &ray, t) {
+                Some(crossing) => crossing,
+                None => break,
+            };
+            ray = next;
+            current = gluing.cell;
+            entered = Some(gluing.face);
+        }
+        pieces
+    }
+
+    /// Thin tubes of the given radius along the loop based at `point` of
+    /// `cell`, each with the cell it should be put in.
+    pub fn tubes(
+        &self,
+        manifold: &CellComplex,
+        cell: usize,
+        point: Vec3,
+        radius: f32,
+    ) -> Vec<(usize, TubeGeometry)> {
+        self.pieces(manifold, cell, point)
+            .into_iter()
+            .filter(|(_, start, end)| (*end - *start).length_sq() > 0.0)
+            .map(|(piece_cell, start, end)| (piece_cell, TubeGeometry::new(start, end, radius)))
+            .collect()
+    }
+}
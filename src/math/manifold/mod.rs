@@ -0,0 +1,10 @@
+pub mod complex;
+pub mod cusp;
+pub mod development;
+pub mod dirichlet;
+pub mod holonomy;
+pub mod ideal;
+pub mod identified_box;
+pub mod loops;
+pub mod triangulation;
+pub mod validation;
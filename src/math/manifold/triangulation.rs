@@ -0,0 +1,517 @@
+use std::ops::Index;
+
+use crate::math::geometry::plane::Plane;
+use crate::math::matrix::Mat4;
+use crate::math::space::Geometry;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// A permutation of the four vertices of a tetrahedron. `images[i]` is the
+/// vertex that vertex `i` is sent to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Permutation {
+    pub images: [usize; 4],
+}
+
+impl Permutation {
+    pub fn new(images: [usize; 4]) -> Permutation {
+        let mut seen = [false; 4];
+        for &image in &images {
+            assert!(image < 4, "Permutation image {} is not a vertex!", image);
+            assert!(!seen[image], "Permutation {:?} is not a bijection!", images);
+            seen[image] = true;
+        }
+        Permutation { images }
+    }
+
+    pub fn identity() -> Permutation {
+        Permutation {
+            images: [0, 1, 2, 3],
+        }
+    }
+
+    pub fn apply(&self, vertex: usize) -> usize {
+        self.images[vertex]
+    }
+
+    pub fn inverse(&self) -> Permutation {
+        let mut images = [0; 4];
+        for (vertex, &image) in self.images.iter().enumerate() {
+            images[image] = vertex;
+        }
+        Permutation { images }
+    }
+
+    /// The permutation which applies `first` and then `self`.
+    pub fn compose(&self, first: &Permutation) -> Permutation {
+        let mut images = [0; 4];
+        for (vertex, image) in images.iter_mut().enumerate() {
+            *image = self.images[first.images[vertex]];
+        }
+        Permutation { images }
+    }
+
+    /// +1 for even permutations and -1 for odd ones.
+    pub fn sign(&self) -> i32 {
+        let mut sign = 1;
+        for i in 0..4 {
+            for j in (i + 1)..4 {
+                if self.images[i] > self.images[j] {
+                    sign = -sign;
+                }
+            }
+        }
+        sign
+    }
+}
+
+impl Index<usize> for Permutation {
+    type Output = usize;
+    fn index(&self, vertex: usize) -> &usize {
+        &self.images[vertex]
+    }
+}
+
+/// Describes how one face of a tetrahedron is identified with a face of
+/// another (or the same) tetrahedron.
+#[derive(Clone, Copy, Debug)]
+pub struct Gluing {
+    pub tetrahedron: usize,
+    pub face: usize,
+    /// Sends the vertices of the source tetrahedron to the vertices of the
+    /// target tetrahedron. The face being glued is always sent to `face`.
+    pub permutation: Permutation,
+}
+
+/// A tetrahedron with its vertices given in its own local chart. Face `i` is
+/// the face opposite vertex `i`.
+#[derive(Clone, Debug)]
+pub struct Tetrahedron {
+    pub vertices: [Vec3; 4],
+    pub gluings: [Option<Gluing>; 4],
+    /// The cone orders marked on each edge (in the order of `EDGES`). An
+    /// edge of order `n` is a cone axis around which the angle is 2 pi / n,
+    /// as in an orbifold. Ordinary edges have order 1.
+    pub cone_orders: [u32; 6],
+}
+
+impl Tetrahedron {
+    pub fn new(vertices: [Vec3; 4]) -> Tetrahedron {
+        Tetrahedron {
+            vertices,
+            gluings: [None; 4],
+            cone_orders: [1; 6],
+        }
+    }
+
+    /// The vertices of a regular tetrahedron centered on the origin whose
+    /// vertices are `circumradius` away from it.
+    pub fn regular_vertices(circumradius: f32) -> [Vec3; 4] {
+        let scale = circumradius / 3.0f32.sqrt();
+        [
+            scale * Vec3::new(1.0, 1.0, 1.0),
+            scale * Vec3::new(1.0, -1.0, -1.0),
+            scale * Vec3::new(-1.0, 1.0, -1.0),
+            scale * Vec3::new(-1.0, -1.0, 1.0),
+        ]
+    }
+
+    /// The six edges of a tetrahedron as pairs of vertices.
+    pub const EDGES: [(usize, usize); 6] = [(0, 1), (0, 2), (0, 3), (1, 2), (1, 3), (2, 3)];
+
+    /// The position of the edge between `a` and `b` in `EDGES`.
+    pub fn edge_index(a: usize, b: usize) -> usize {
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        Tetrahedron::EDGES
+            .iter()
+            .position(|&edge| edge == (a, b))
+            .expect("Not an edge of a tetrahedron!")
+    }
+
+    /// The two vertices which are not on the edge between `a` and `b`.
+    pub fn opposite_edge(a: usize, b: usize) -> (usize, usize) {
+        let mut others = (0..4).filter(|&vertex| vertex != a && vertex != b);
+        (others.next().unwrap(), others.next().unwrap())
+    }
+
+    /// The indices of the vertices on `face`, in increasing order.
+    pub fn face_vertices(face: usize) -> [usize; 3] {
+        match face {
+            0 => [1, 2, 3],
+            1 => [0, 2, 3],
+            2 => [0, 1, 3],
+            3 => [0, 1, 2],
+            _ => panic!("Invalid face for tetrahedron!"),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.vertices[0] + self.vertices[1] + self.vertices[2] + self.vertices[3]) / 4.0
+    }
+
+    /// The plane containing `face` with its normal pointing out of the
+    /// tetrahedron.
+    pub fn face_plane(&self, face: usize) -> Plane {
+        let [a, b, c] = Tetrahedron::face_vertices(face);
+        let origin = self.vertices[a];
+        let mut normal = (self.vertices[b] - origin)
+            .cross(&(self.vertices[c] - origin))
+            .normalized();
+        if normal.dot(&(self.vertices[face] - origin)) > 0.0 {
+            normal = -normal;
+        }
+        Plane::through(origin, normal)
+    }
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// A three-manifold built out of tetrahedra whose faces are glued in pairs.
+/// The vertices of each tetrahedron are given in the chart of `geometry`, so
+/// a hyperbolic triangulation has its vertices inside the Klein ball.
+#[derive(Clone, Debug, Default)]
+pub struct Triangulation {
+    pub tetrahedra: Vec<Tetrahedron>,
+    pub geometry: Geometry,
+}
+
+impl Triangulation {
+    pub fn new() -> Triangulation {
+        Triangulation::with_geometry(Geometry::Euclidean)
+    }
+
+    pub fn with_geometry(geometry: Geometry) -> Triangulation {
+        Triangulation {
+            tetrahedra: Vec::new(),
+            geometry,
+        }
+    }
+
+    /// The 3-sphere as the boundary of a 4-simplex: five regular spherical
+    /// tetrahedra, each of which is glued to all of the others.
+    pub fn three_sphere() -> Triangulation {
+        // Seen from the center of a tetrahedron its vertices are
+        // arccos(1/4) away, which the gnomonic chart puts at sqrt(15).
+        let vertices = Tetrahedron::regular_vertices(15.0f32.sqrt());
+        // The vertices of the simplex each tetrahedron is made of. Every
+        // other tetrahedron is flipped so that they are oriented coherently.
+        let corners = |tetrahedron: usize| {
+            let mut corners = [0; 4];
+            let others = (0..5).filter(|&vertex| vertex != tetrahedron);
+            for (corner, vertex) in corners.iter_mut().zip(others) {
+                *corner = vertex;
+            }
+            if tetrahedron % 2 == 1 {
+                corners.swap(0, 1);
+            }
+            corners
+        };
+        let mut triangulation = Triangulation::with_geometry(Geometry::Spherical);
+        for _ in 0..5 {
+            triangulation.add_tetrahedron(vertices);
+        }
+        for tetrahedron in 0..5 {
+            let from = corners(tetrahedron);
+            for (face, &target) in from.iter().enumerate() {
+                if target < tetrahedron {
+                    continue;
+                }
+                let to = corners(target);
+                let local = |vertex: usize| to.iter().position(|&x| x == vertex).unwrap();
+                let mut images = [0; 4];
+                for (image, &vertex) in images.iter_mut().zip(from.iter()) {
+                    *image = local(if vertex == target {
+                        tetrahedron
+                    } else {
+                        vertex
+                    });
+                }
+                triangulation.glue(tetrahedron, face, target, Permutation::new(images));
+            }
+        }
+        triangulation
+    }
+
+    /// The lens space L(p, q): the quotient of the 3-sphere, as the unit
+    /// sphere of C^2, by `(z, w) -> (e^(2 pi i q / p) z, e^(2 pi i / p) w)`.
+    /// The 3-sphere is the join of the unit circles in the two coordinates,
+    /// so cutting both circles into arcs cuts it into tetrahedra, each the
+    /// join of an arc of one circle with an arc of the other. All of the
+    /// first circle joined with a p-th of the second is a fundamental domain.
+    pub fn lens_space(p: u32, q: u32) -> Triangulation {
+        assert!(p > 0 && gcd(p, q) == 1, "Lens spaces need coprime p and q!");
+        // Arcs must be shorter than pi for their tetrahedra to fit in a
+        // chart, so each p-th of a circle is cut into `k` arcs.
+        let k = 3u32.div_ceil(p) as usize;
+        let arcs = p as usize * k;
+        let shift = (q % p) as usize * k;
+        let angle = 2.0 * std::f32::consts::PI / arcs as f32;
+
+        // Every tetrahedron is a rotation of the first, which joins the arc
+        // from (1, 0) to (e^(i angle), 0) with the arc from (0, 1) to
+        // (0, e^(i angle)). Its chart is centered on the middle of the two.
+        let (sine, cosine) = (0.5 * angle).sin_cos();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let center = Vec4::new(cosine, sine, cosine, sine) * half;
+        let axes = [
+            Vec4::new(cosine, sine, -cosine, -sine) * half,
+            Vec4::new(-sine, cosine, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, -sine, cosine),
+        ];
+        let chart = |point: Vec4| {
+            Vec3::new(
+                axes[0].dot(&point),
+                axes[1].dot(&point),
+                axes[2].dot(&point),
+            ) / center.dot(&point)
+        };
+        let vertices = [
+            chart(Vec4::new(1.0, 0.0, 0.0, 0.0)),
+            chart(Vec4::new(angle.cos(), angle.sin(), 0.0, 0.0)),
+            chart(Vec4::new(0.0, 0.0, 1.0, 0.0)),
+            chart(Vec4::new(0.0, 0.0, angle.cos(), angle.sin())),
+        ];
+
+        // Tetrahedron `i * arcs + j` joins the `j`th arc of the first
+        // circle with the `i`th arc of the second.
+        let mut triangulation = Triangulation::with_geometry(Geometry::Spherical);
+        for _ in 0..arcs * k {
+            triangulation.add_tetrahedron(vertices);
+        }
+        for i in 0..k {
+            for j in 0..arcs {
+                let tetrahedron = i * arcs + j;
+                // Step along the first circle.
+                let next = i * arcs + (j + 1) % arcs;
+                triangulation.glue(tetrahedron, 0, next, Permutation::new([1, 0, 2, 3]));
+                // Step along the second, which past the end of the domain
+                // means coming back in at the start, turned by the inverse
+                // of the generator.
+                let next = if i + 1 < k {
+                    tetrahedron + arcs
+                } else {
+                    (j + arcs - shift) % arcs
+                };
+                triangulation.glue(tetrahedron, 2, next, Permutation::new([0, 1, 3, 2]));
+            }
+        }
+        triangulation
+    }
+
+    pub fn size(&self) -> usize {
+        self.tetrahedra.len()
+    }
+
+    pub fn add_tetrahedron(&mut self, vertices: [Vec3; 4]) -> usize {
+        self.tetrahedra.push(Tetrahedron::new(vertices));
+        self.tetrahedra.len() - 1
+    }
+
+    /// Glue `face` of `tetrahedron` to the face `permutation[face]` of
+    /// `target`. The reverse gluing is recorded on the target as well, so each
+    /// pair of faces only needs to be glued once.
+    pub fn glue(
+        &mut self,
+        tetrahedron: usize,
+        face: usize,
+        target: usize,
+        permutation: Permutation,
+    ) {
+        let target_face = permutation[face];
+        assert!(
+            tetrahedron != target || face != target_face,
+            "Cannot glue a face to itself!"
+        );
+        self.unglue(tetrahedron, face);
+        self.unglue(target, target_face);
+        self.tetrahedra[tetrahedron].gluings[face] = Some(Gluing {
+            tetrahedron: target,
+            face: target_face,
+            permutation,
+        });
+        self.tetrahedra[target].gluings[target_face] = Some(Gluing {
+            tetrahedron,
+            face,
+            permutation: permutation.inverse(),
+        });
+    }
+
+    /// Make `face` of `tetrahedron` a mirror, glued to itself by reflecting
+    /// across it, as in an orbifold.
+    pub fn mirror(&mut self, tetrahedron: usize, face: usize) {
+        self.unglue(tetrahedron, face);
+        self.tetrahedra[tetrahedron].gluings[face] = Some(Gluing {
+            tetrahedron,
+            face,
+            permutation: Permutation::identity(),
+        });
+    }
+
+    /// Whether `face` of `tetrahedron` is a mirror.
+    pub fn is_mirror(&self, tetrahedron: usize, face: usize) -> bool {
+        self.tetrahedra[tetrahedron].gluings[face]
+            .is_some_and(|gluing| gluing.tetrahedron == tetrahedron && gluing.face == face)
+    }
+
+    /// Make the edge between vertices `a` and `b` of `tetrahedron` a cone
+    /// axis with angle 2 pi / `order` around it. Marking the edge in any one
+    /// of the tetrahedra around it marks the whole edge of the triangulation.
+    pub fn set_cone_order(&mut self, tetrahedron: usize, a: usize, b: usize, order: u32) {
+        assert!(order > 0, "Cone orders must be positive!");
+        self.tetrahedra[tetrahedron].cone_orders[Tetrahedron::edge_index(a, b)] = order;
+    }
+
+    /// The cone order of the edge of the triangulation in `class`, as given
+    /// by `edge_classes`.
+    pub fn cone_order(&self, class: &[(usize, usize, usize)]) -> u32 {
+        class
+            .iter()
+            .map(|&(tetrahedron, a, b)| {
+                self.tetrahedra[tetrahedron].cone_orders[Tetrahedron::edge_index(a, b)]
+            })
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Remove the gluing on `face` of `tetrahedron`, along with its partner.
+    pub fn unglue(&mut self, tetrahedron: usize, face: usize) {
+        if let Some(gluing) = self.tetrahedra[tetrahedron].gluings[face].take() {
+            let partner = &mut self.tetrahedra[gluing.tetrahedron].gluings[gluing.face];
+            if let Some(back) = partner {
+                if back.tetrahedron == tetrahedron && back.face == face {
+                    *partner = None;
+                }
+            }
+        }
+    }
+
+    /// The isometry taking the chart of `tetrahedron` to the chart of the
+    /// tetrahedron glued onto `face`. The vertices of the face are sent to
+    /// their images under the gluing permutation, and the outward normal of
+    /// the face is sent to the inward normal of the target face. Both are
+    /// taken in the model of the geometry, where isometries are linear.
+    pub fn gluing_isometry(&self, tetrahedron: usize, face: usize) -> Option<Mat4> {
+        let geometry = self.geometry;
+        let source = &self.tetrahedra[tetrahedron];
+        let gluing = source.gluings[face]?;
+        let target = &self.tetrahedra[gluing.tetrahedron];
+        let [a, b, c] = Tetrahedron::face_vertices(face);
+        let from = Mat4::from_columns([
+            geometry.lift(source.vertices[a]),
+            geometry.lift(source.vertices[b]),
+            geometry.lift(source.vertices[c]),
+            geometry.plane_pole(&source.face_plane(face)),
+        ]);
+        let mut images = [
+            geometry.lift(target.vertices[gluing.permutation[a]]),
+            geometry.lift(target.vertices[gluing.permutation[b]]),
+            geometry.lift(target.vertices[gluing.permutation[c]]),
+        ];
+        if geometry == Geometry::Hyperbolic {
+            // Ideal vertices only lift to the light cone up to scale, so pick
+            // the scales which make the map preserve the inner products
+            // between the three vertices. Finite vertices get a scale of 1.
+            let ratio = |i: usize, j: usize| {
+                geometry.inner(&from.column(i), &from.column(j))
+                    / geometry.inner(&images[i], &images[j])
+            };
+            let (ab, ac, bc) = (ratio(0, 1), ratio(0, 2), ratio(1, 2));
+            images[0] *= (ab * ac / bc).sqrt();
+            images[1] *= (ab * bc / ac).sqrt();
+            images[2] *= (ac * bc / ab).sqrt();
+        }
+        let to = Mat4::from_columns([
+            images[0],
+            images[1],
+            images[2],
+            -geometry.plane_pole(&target.face_plane(gluing.face)),
+        ]);
+        from.inverse().map(|inverse| to * inverse)
+    }
+
+    /// Group the edges of all tetrahedra into the edges of the manifold.
+    /// Each class lists the `(tetrahedron, a, b)` edges met walking around
+    /// it, with `a` and `b` carried along so that each edge is traversed the
+    /// same way. A walk which runs into an unglued face stops there, while
+    /// one which runs into a mirror turns back, so edges on mirrors are
+    /// listed twice.
+    pub fn edge_classes(&self) -> Vec<Vec<(usize, usize, usize)>> {
+        let mut visited = vec![[false; 6]; self.size()];
+        let mut classes = Vec::new();
+        for tetrahedron in 0..self.size() {
+            for (index, &(a, b)) in Tetrahedron::EDGES.iter().enumerate() {
+                if visited[tetrahedron][index] {
+                    continue;
+                }
+                let mut class = Vec::new();
+                // The walk leaves each tetrahedron through the face opposite
+                // `exit`, which contains the edge.
+                let start = (tetrahedron, a, b, Tetrahedron::opposite_edge(a, b).1);
+                let mut state = start;
+                for _ in 0..6 * self.size() {
+                    let (current, a, b, exit) = state;
+                    visited[current][Tetrahedron::edge_index(a, b)] = true;
+                    class.push((current, a, b));
+                    let gluing = match self.tetrahedra[current].gluings[exit] {
+                        Some(gluing) => gluing,
+                        None => break,
+                    };
+                    // The next face to leave through is the other one on the
+                    // edge, which is opposite the face just entered.
+                    let (c, d) = Tetrahedron::opposite_edge(a, b);
+                    let other = if exit == d { c } else { d };
+                    let p = gluing.permutation;
+                    state = (gluing.tetrahedron, p[a], p[b], p[other]);
+                    if state.0 == start.0
+                        && state.3 == start.3
+                        && Tetrahedron::edge_index(state.1, state.2) == index
+                    {
+                        break;
+                    }
+                }
+                classes.push(class);
+            }
+        }
+        classes
+    }
+
+    /// Whether the tetrahedra can be given orientations which every gluing
+    /// respects. Gluing two faces of tetrahedra oriented the same way keeps
+    /// the orientation exactly when the permutation is odd. Mirrors always
+    /// reverse it.
+    pub fn is_orientable(&self) -> bool {
+        let mut orientations = vec![0; self.size()];
+        for start in 0..self.size() {
+            if orientations[start] != 0 {
+                continue;
+            }
+            orientations[start] = 1;
+            let mut stack = vec![start];
+            while let Some(tetrahedron) = stack.pop() {
+                for gluing in self.tetrahedra[tetrahedron].gluings.iter().flatten() {
+                    let wanted = -orientations[tetrahedron] * gluing.permutation.sign();
+                    match orientations[gluing.tetrahedron] {
+                        0 => {
+                            orientations[gluing.tetrahedron] = wanted;
+                            stack.push(gluing.tetrahedron);
+                        }
+                        found if found != wanted => return false,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tetrahedra
+            .iter()
+            .all(|tetrahedron| tetrahedron.gluings.iter().all(|x| x.is_some()))
+    }
+}
@@ -0,0 +1,303 @@
+use std::fmt;
+
+use crate::math::manifold::triangulation::{Permutation, Tetrahedron, Triangulation};
+use crate::math::space::Geometry;
+
+/// Something wrong with a triangulation which would stop it from being a
+/// manifold, or from rendering as one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TriangulationError {
+    /// The face is not glued to anything.
+    Unglued { tetrahedron: usize, face: usize },
+    /// The face it is glued to does not glue back to it.
+    OneSided { tetrahedron: usize, face: usize },
+    /// The gluing permutation does not send the face onto the face it is
+    /// glued to.
+    WrongFace { tetrahedron: usize, face: usize },
+    /// Gluing across the face and back again does not give the identity.
+    NotInvolutive { tetrahedron: usize, face: usize },
+    /// The dihedral angles around the edge between the two vertices of the
+    /// tetrahedron add up to `angle` rather than 2 pi divided by the cone
+    /// order of the edge.
+    EdgeAngle {
+        tetrahedron: usize,
+        edge: (usize, usize),
+        angle: f32,
+        order: u32,
+    },
+    /// The triangulation does not have Euler characteristic 0.
+    EulerCharacteristic(i64),
+}
+
+impl fmt::Display for TriangulationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TriangulationError::*;
+        match self {
+            Unglued { tetrahedron, face } => write!(
+                f,
+                "face {} of tetrahedron {} is not glued",
+                face, tetrahedron
+            ),
+            OneSided { tetrahedron, face } => write!(
+                f,
+                "face {} of tetrahedron {} is not glued back to",
+                face, tetrahedron
+            ),
+            WrongFace { tetrahedron, face } => write!(
+                f,
+                "the gluing on face {} of tetrahedron {} sends it to the wrong face",
+                face, tetrahedron
+            ),
+            NotInvolutive { tetrahedron, face } => write!(
+                f,
+                "the gluings on either side of face {} of tetrahedron {} are not inverses",
+                face, tetrahedron
+            ),
+            EdgeAngle {
+                tetrahedron,
+                edge,
+                angle,
+                order: 1,
+            } => write!(
+                f,
+                "the edge {}-{} of tetrahedron {} has total dihedral angle {} instead of 2 pi",
+                edge.0, edge.1, tetrahedron, angle
+            ),
+            EdgeAngle {
+                tetrahedron,
+                edge,
+                angle,
+                order,
+            } => write!(
+                f,
+                "the cone edge {}-{} of tetrahedron {} has total dihedral angle {} instead of 2 pi / {}",
+                edge.0, edge.1, tetrahedron, angle, order
+            ),
+            EulerCharacteristic(chi) => {
+                write!(f, "the Euler characteristic is {} instead of 0", chi)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TriangulationError {}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[index] = root;
+    root
+}
+
+impl Triangulation {
+    /// The interior dihedral angle of `tetrahedron` along the edge between
+    /// vertices `a` and `b`, measured in the triangulation's geometry.
+    pub fn dihedral_angle(&self, tetrahedron: usize, a: usize, b: usize) -> f32 {
+        let geometry = self.geometry;
+        let tetrahedron = &self.tetrahedra[tetrahedron];
+        let (c, d) = Tetrahedron::opposite_edge(a, b);
+        let first = geometry.plane_pole(&tetrahedron.face_plane(c));
+        let second = geometry.plane_pole(&tetrahedron.face_plane(d));
+        (-geometry.inner(&first, &second)).clamp(-1.0, 1.0).acos()
+    }
+
+    /// Check that the gluings match up in pairs.
+    fn validate_gluings(&self, errors: &mut Vec<TriangulationError>) {
+        for (tetrahedron, source) in self.tetrahedra.iter().enumerate() {
+            for face in 0..4 {
+                let gluing = if let Some(gluing) = source.gluings[face] {
+                    gluing
+                } else {
+                    errors.push(TriangulationError::Unglued { tetrahedron, face });
+                    continue;
+                };
+                let back = self
+                    .tetrahedra
+                    .get(gluing.tetrahedron)
+                    .and_then(|target| target.gluings.get(gluing.face).copied().flatten());
+                match back {
+                    Some(back) if back.tetrahedron == tetrahedron && back.face == face => {
+                        if gluing.permutation[face] != gluing.face {
+                            errors.push(TriangulationError::WrongFace { tetrahedron, face });
+                        } else if back.permutation.compose(&gluing.permutation)
+                            != Permutation::identity()
+                        {
+                            errors.push(TriangulationError::NotInvolutive { tetrahedron, face });
+                        }
+                    }
+                    _ => errors.push(TriangulationError::OneSided { tetrahedron, face }),
+                }
+            }
+        }
+    }
+
+    /// Add up dihedral angles around every edge class. Returns the number
+    /// of edge classes.
+    fn validate_edges(&self, errors: &mut Vec<TriangulationError>) -> usize {
+        let classes = self.edge_classes();
+        for class in &classes {
+            let angle: f32 = class
+                .iter()
+                .map(|&(tetrahedron, a, b)| self.dihedral_angle(tetrahedron, a, b))
+                .sum();
+            let order = self.cone_order(class);
+            if (angle - 2.0 * std::f32::consts::PI / order as f32).abs() > 1e-3 {
+                let (tetrahedron, a, b) = class[0];
+                errors.push(TriangulationError::EdgeAngle {
+                    tetrahedron,
+                    edge: (a, b),
+                    angle,
+                    order,
+                });
+            }
+        }
+        classes.len()
+    }
+
+    /// The number of vertex classes, leaving out ideal vertices which sit on
+    /// the sphere at infinity of a hyperbolic triangulation.
+    fn count_vertices(&self) -> usize {
+        let mut parents: Vec<usize> = (0..4 * self.size()).collect();
+        for (tetrahedron, source) in self.tetrahedra.iter().enumerate() {
+            for (face, gluing) in source.gluings.iter().enumerate() {
+                let gluing = if let Some(gluing) = gluing {
+                    gluing
+                } else {
+                    continue;
+                };
+                for vertex in Tetrahedron::face_vertices(face).iter() {
+                    let here = find(&mut parents, 4 * tetrahedron + vertex);
+                    let there = find(
+                        &mut parents,
+                        4 * gluing.tetrahedron + gluing.permutation[*vertex],
+                    );
+                    parents[here] = there;
+                }
+            }
+        }
+        let mut roots: Vec<usize> = (0..4 * self.size())
+            .filter(|&index| {
+                let vertex = self.tetrahedra[index / 4].vertices[index % 4];
+                self.geometry != Geometry::Hyperbolic || vertex.length_sq() < 1.0 - 1e-4
+            })
+            .map(|index| find(&mut parents, index))
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+        roots.len()
+    }
+
+    /// Check that the triangulation describes a manifold (or orbifold) in
+    /// its geometry: every face is glued to exactly one other face by
+    /// inverse gluings or is a mirror, the dihedral angles around every edge
+    /// add up to 2 pi over its cone order and, when there are no mirrors,
+    /// the Euler characteristic vanishes. Every problem found is returned.
+    pub fn validate(&self) -> Result<(), Vec<TriangulationError>> {
+        let mut errors = Vec::new();
+        self.validate_gluings(&mut errors);
+        // Walking around edges needs every gluing to be in place.
+        if errors.is_empty() {
+            let edges = self.validate_edges(&mut errors) as i64;
+            let vertices = self.count_vertices() as i64;
+            let tetrahedra = self.size() as i64;
+            let faces = 2 * tetrahedra;
+            let chi = vertices - edges + faces - tetrahedra;
+            // Mirrors are boundary of the underlying space, which then need
+            // not have vanishing Euler characteristic.
+            let mirrored = (0..self.size())
+                .any(|tetrahedron| (0..4).any(|face| self.is_mirror(tetrahedron, face)));
+            if chi != 0 && !mirrored {
+                errors.push(TriangulationError::EulerCharacteristic(chi));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::regina;
+    use crate::math::complex::Complex;
+    use crate::math::manifold::ideal::IdealTriangulation;
+
+    fn figure_eight() -> Triangulation {
+        let combinatorics = regina::from_isosig("cPcbbbiht").unwrap();
+        let regular = Complex::from_polar(1.0, std::f32::consts::FRAC_PI_3);
+        IdealTriangulation::new(&combinatorics, vec![regular; 2]).triangulation
+    }
+
+    #[test]
+    fn manifolds_are_valid() {
+        assert_eq!(Triangulation::three_sphere().validate(), Ok(()));
+        assert_eq!(Triangulation::lens_space(5, 2).validate(), Ok(()));
+        assert_eq!(figure_eight().validate(), Ok(()));
+    }
+
+    #[test]
+    fn unglued_faces_are_reported_on_both_sides() {
+        let mut triangulation = Triangulation::three_sphere();
+        let partner = triangulation.tetrahedra[0].gluings[0].unwrap();
+        triangulation.unglue(0, 0);
+        assert_eq!(
+            triangulation.validate(),
+            Err(vec![
+                TriangulationError::Unglued {
+                    tetrahedron: 0,
+                    face: 0
+                },
+                TriangulationError::Unglued {
+                    tetrahedron: partner.tetrahedron,
+                    face: partner.face
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn broken_permutations_are_reported() {
+        // Swapping two vertices of the face still sends it to the right
+        // face, but the gluing back no longer undoes it.
+        let mut triangulation = Triangulation::three_sphere();
+        let gluing = triangulation.tetrahedra[0].gluings[0].as_mut().unwrap();
+        gluing.permutation = gluing.permutation.compose(&Permutation::new([0, 2, 1, 3]));
+        let errors = triangulation.validate().unwrap_err();
+        assert!(errors.contains(&TriangulationError::NotInvolutive {
+            tetrahedron: 0,
+            face: 0
+        }));
+
+        // Swapping the face's own vertex off it sends it elsewhere.
+        let mut triangulation = Triangulation::three_sphere();
+        let gluing = triangulation.tetrahedra[0].gluings[0].as_mut().unwrap();
+        gluing.permutation = gluing.permutation.compose(&Permutation::new([1, 0, 2, 3]));
+        let errors = triangulation.validate().unwrap_err();
+        assert!(errors.contains(&TriangulationError::WrongFace {
+            tetrahedron: 0,
+            face: 0
+        }));
+    }
+
+    #[test]
+    fn moved_vertices_break_edge_angles() {
+        let mut triangulation = Triangulation::three_sphere();
+        triangulation.tetrahedra[0].vertices[0] *= 0.8;
+        let errors = triangulation.validate().unwrap_err();
+        assert!(!errors.is_empty());
+        for error in &errors {
+            match error {
+                TriangulationError::EdgeAngle { angle, order, .. } => {
+                    assert_eq!(*order, 1);
+                    assert!((angle - 2.0 * std::f32::consts::PI).abs() > 1e-3);
+                }
+                _ => panic!("unexpected error {}", error),
+            }
+        }
+    }
+}
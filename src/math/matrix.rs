@@ -0,0 +1,278 @@
+use std::ops::Mul;
+
+use crate::math::quaternion::Quaternion;
+use crate::math::ray::Ray;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// A 4x4 matrix acting on homogeneous coordinates. Stored in row major order.
+#[derive(Clone, Copy, Debug)]
+pub struct Mat4 {
+    pub rows: [[f32; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        Mat4 {
+            rows: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn from_rows(rows: [[f32; 4]; 4]) -> Mat4 {
+        Mat4 { rows }
+    }
+
+    pub fn from_columns(columns: [Vec4; 4]) -> Mat4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (col, column) in columns.iter().enumerate() {
+            for (row, entries) in rows.iter_mut().enumerate() {
+                entries[col] = column[row];
+            }
+        }
+        Mat4 { rows }
+    }
+
+    pub fn translation(offset: Vec3) -> Mat4 {
+        let mut matrix = Mat4::identity();
+        matrix.rows[0][3] = offset.x;
+        matrix.rows[1][3] = offset.y;
+        matrix.rows[2][3] = offset.z;
+        matrix
+    }
+
+    /// Build an affine map from the top three rows, as a 3x4 matrix.
+    pub fn affine(rows: [[f32; 4]; 3]) -> Mat4 {
+        Mat4 {
+            rows: [rows[0], rows[1], rows[2], [0.0, 0.0, 0.0, 1.0]],
+        }
+    }
+
+    /// Stretch each axis by the matching factor.
+    pub fn scale(factors: Vec3) -> Mat4 {
+        let mut matrix = Mat4::identity();
+        matrix.rows[0][0] = factors.x;
+        matrix.rows[1][1] = factors.y;
+        matrix.rows[2][2] = factors.z;
+        matrix
+    }
+
+    pub fn rotation(rotation: Quaternion) -> Mat4 {
+        Mat4::from_columns([
+            Vec4::direction(rotation * Vec3::right()),
+            Vec4::direction(rotation * Vec3::up()),
+            Vec4::direction(rotation * Vec3::forward()),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        ])
+    }
+
+    /// Mirror across the plane through the origin with the given normal.
+    pub fn reflection(normal: Vec3) -> Mat4 {
+        let normal = normal.normalized();
+        let reflect = |axis: Vec3| Vec4::direction(axis - 2.0 * axis.dot(&normal) * normal);
+        Mat4::from_columns([
+            reflect(Vec3::right()),
+            reflect(Vec3::up()),
+            reflect(Vec3::forward()),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        ])
+    }
+
+    pub fn column(&self, col: usize) -> Vec4 {
+        Vec4::new(
+            self.rows[0][col],
+            self.rows[1][col],
+            self.rows[2][col],
+            self.rows[3][col],
+        )
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (row, entries) in rows.iter_mut().enumerate() {
+            for (col, entry) in entries.iter_mut().enumerate() {
+                *entry = self.rows[col][row];
+            }
+        }
+        Mat4 { rows }
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let mut det = 0.0;
+        for (col, entry) in self.rows[0].iter().enumerate() {
+            let sign = if col % 2 == 0 { 1.0 } else { -1.0 };
+            det += sign * entry * self.minor(0, col);
+        }
+        det
+    }
+
+    /// Determinant of the 3x3 matrix left after removing `row` and `col`.
+    fn minor(&self, row: usize, col: usize) -> f32 {
+        let mut sub = [[0.0; 3]; 3];
+        let mut sub_row = 0;
+        for r in 0..4 {
+            if r == row {
+                continue;
+            }
+            let mut sub_col = 0;
+            for c in 0..4 {
+                if c == col {
+                    continue;
+                }
+                sub[sub_row][sub_col] = self.rows[r][c];
+                sub_col += 1;
+            }
+            sub_row += 1;
+        }
+        sub[0][0] * (sub[1][1] * sub[2][2] - sub[1][2] * sub[2][1])
+            - sub[0][1] * (sub[1][0] * sub[2][2] - sub[1][2] * sub[2][0])
+            + sub[0][2] * (sub[1][0] * sub[2][1] - sub[1][1] * sub[2][0])
+    }
+
+    /// Inverse using Gauss-Jordan elimination with partial pivoting. Returns
+    /// `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut m = self.rows;
+        let mut inv = Mat4::identity().rows;
+        for col in 0..4 {
+            let mut pivot = col;
+            for row in (col + 1)..4 {
+                if m[row][col].abs() > m[pivot][col].abs() {
+                    pivot = row;
+                }
+            }
+            if m[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1.0 / m[col][col];
+            for k in 0..4 {
+                m[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = m[row][col];
+                if factor != 0.0 {
+                    for k in 0..4 {
+                        m[row][k] -= factor * m[col][k];
+                        inv[row][k] -= factor * inv[col][k];
+                    }
+                }
+            }
+        }
+        Some(Mat4 { rows: inv })
+    }
+
+    /// The matrix product `self * rhs`, which applies `rhs` first.
+    pub fn compose(&self, rhs: &Mat4) -> Mat4 {
+        let mut rows = [[0.0; 4]; 4];
+        for (row, entries) in rows.iter_mut().enumerate() {
+            for (col, entry) in entries.iter_mut().enumerate() {
+                *entry = (0..4).map(|k| self.rows[row][k] * rhs.rows[k][col]).sum();
+            }
+        }
+        Mat4 { rows }
+    }
+
+    /// The sign a matrix standing for a map of the chart is taken to have, as
+    /// a matrix and its negative are the same map. The bottom right entry
+    /// decides where it is clear of zero, which it is for every Euclidean and
+    /// hyperbolic isometry, and otherwise the first entry which is.
+    pub fn sign(&self) -> f32 {
+        let largest = self
+            .rows
+            .iter()
+            .flatten()
+            .fold(0.0f32, |largest, entry| largest.max(entry.abs()));
+        let clear = |entry: f32| entry.abs() > 1e-3 * largest;
+        let corner = self.rows[3][3];
+        let decider = if clear(corner) {
+            corner
+        } else {
+            self.rows
+                .iter()
+                .flatten()
+                .copied()
+                .find(|&entry| clear(entry))
+                .unwrap_or(1.0)
+        };
+        decider.signum()
+    }
+
+    /// Whether the two matrices are the same map of the chart, up to sign
+    /// and the error picked up composing them. Entries are compared relative
+    /// to the largest, since isometries which move far in hyperbolic space
+    /// have huge entries.
+    pub fn same_map(&self, other: &Mat4) -> bool {
+        let (sign, other_sign) = (self.sign(), other.sign());
+        let mut entries = self.rows.iter().flatten().zip(other.rows.iter().flatten());
+        let scale = entries
+            .clone()
+            .fold(1.0f32, |scale, (a, b)| scale.max(a.abs()).max(b.abs()));
+        entries.all(|(a, b)| (sign * a - other_sign * b).abs() <= 1e-3 * scale)
+    }
+
+    /// Apply the matrix to a point given in (dehomogenized) chart coordinates.
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        (self * Vec4::point(point)).dehomogenize()
+    }
+
+    /// Apply the matrix to a direction, which translations leave alone.
+    pub fn transform_direction(&self, direction: Vec3) -> Vec3 {
+        (self * Vec4::direction(direction)).xyz()
+    }
+
+    /// Apply the matrix to a ray. Projective maps send lines to lines, so the
+    /// new direction is the derivative of the image of the ray at its origin.
+    pub fn transform_ray(&self, ray: &Ray) -> Ray {
+        let origin = self * Vec4::point(ray.origin);
+        let toward = self * Vec4::direction(ray.direction);
+        let direction = toward.xyz() * origin.w - origin.xyz() * toward.w;
+        Ray {
+            origin: origin.dehomogenize(),
+            direction: direction.normalized(),
+            ..*ray
+        }
+    }
+}
+
+impl Mul<Vec4> for &Mat4 {
+    type Output = Vec4;
+    fn mul(self, rhs: Vec4) -> Vec4 {
+        let m = &self.rows;
+        Vec4::new(
+            m[0][0] * rhs.x + m[0][1] * rhs.y + m[0][2] * rhs.z + m[0][3] * rhs.w,
+            m[1][0] * rhs.x + m[1][1] * rhs.y + m[1][2] * rhs.z + m[1][3] * rhs.w,
+            m[2][0] * rhs.x + m[2][1] * rhs.y + m[2][2] * rhs.z + m[2][3] * rhs.w,
+            m[3][0] * rhs.x + m[3][1] * rhs.y + m[3][2] * rhs.z + m[3][3] * rhs.w,
+        )
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+    fn mul(self, rhs: Vec4) -> Vec4 {
+        &self * rhs
+    }
+}
+
+impl Mul<&Mat4> for &Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: &Mat4) -> Mat4 {
+        self.compose(rhs)
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        self.compose(&rhs)
+    }
+}
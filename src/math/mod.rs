@@ -2,7 +2,12 @@
 pub mod vectorized_impls;
 pub mod colliders;
 pub mod colors;
+pub mod complex;
 pub mod geometry;
+pub mod manifold;
+pub mod matrix;
 pub mod quaternion;
 pub mod ray;
+pub mod space;
+pub mod thurston;
 pub mod vectors;
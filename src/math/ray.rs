@@ -1,11 +1,21 @@
 use crate::math::geometry::aabb::AABBGeometry;
+use crate::math::manifold::holonomy::Holonomy;
+use crate::math::space::Geometry;
 use crate::math::vectors::Vec3;
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub cast_time: f32,
+    /// The geometry the ray travels through. Colliders which are not flat
+    /// use this to decide what their shape actually is.
+    pub geometry: Geometry,
+    /// Whether the ray's frame has been reflected since it left the camera,
+    /// by crossing a gluing (or scattering off a surface) which reverses
+    /// orientation. The picture behind a mirror comes out mirrored on its
+    /// own; this records that it has, so handed things can tell.
+    pub mirrored: bool,
 }
 
 impl Ray {
@@ -14,6 +24,8 @@ impl Ray {
             origin: origin,
             direction: direction.normalized(),
             cast_time: 0.0,
+            geometry: Geometry::Euclidean,
+            mirrored: false,
         }
     }
 
@@ -22,6 +34,8 @@ impl Ray {
             origin: origin,
             direction: (target - origin).normalized(),
             cast_time: 0.0,
+            geometry: Geometry::Euclidean,
+            mirrored: false,
         }
     }
 
@@ -32,6 +46,18 @@ impl Ray {
         }
     }
 
+    pub fn in_geometry(self, geometry: Geometry) -> Ray {
+        Ray { geometry, ..self }
+    }
+
+    /// This ray, with the handedness of `other`.
+    pub fn oriented_like(self, other: &Ray) -> Ray {
+        Ray {
+            mirrored: other.mirrored,
+            ..self
+        }
+    }
+
     pub fn point_at_parameter(&self, time: f32) -> Vec3 {
         self.origin + time * self.direction
     }
@@ -44,6 +70,11 @@ pub struct RayHit {
     pub normal: Vec3,
     pub u: f32,
     pub v: f32,
+    /// Whether the ray which found the hit was mirrored.
+    pub mirrored: bool,
+    /// The gluings crossed on the way to the hit. Colliders leave this empty
+    /// and tracing through a manifold fills it in.
+    pub holonomy: Holonomy,
 }
 
 use std::fmt;
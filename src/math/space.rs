@@ -0,0 +1,328 @@
+use crate::math::geometry::plane::Plane;
+use crate::math::matrix::Mat4;
+use crate::math::ray::Ray;
+use crate::math::vectors::{Vec3, Vec4};
+
+/// The geometry a scene is rendered in.
+///
+/// Every geometry is drawn in a projective chart: Euclidean space in its usual
+/// coordinates, hyperbolic space in the Klein model (the unit ball) and the
+/// 3-sphere in the gnomonic projection of the hemisphere `w > 0`. In these
+/// charts geodesics are straight lines and totally geodesic planes are
+/// ordinary planes, so rays, planes and rects all work unchanged. Distances
+/// and angles do not, which is what the functions here are for. Points of the
+/// chart lift to the model of the space in four dimensions (the hyperboloid
+/// `x^2 + y^2 + z^2 - w^2 = -1` for hyperbolic space, the unit sphere of R^4
+/// for spherical space), where isometries are 4x4 matrices.
+///
+/// A single spherical chart only sees half of the 3-sphere. To see all of it
+/// (and to have rays wrap around) render inside a manifold whose cells each
+/// fit in a chart, such as `Triangulation::three_sphere`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Geometry {
+    #[default]
+    Euclidean,
+    Hyperbolic,
+    Spherical,
+}
+
+/// A unit speed geodesic in the four dimensional model of a space.
+#[derive(Clone, Copy, Debug)]
+pub struct Geodesic {
+    pub position: Vec4,
+    pub tangent: Vec4,
+}
+
+impl Geodesic {
+    /// The point a distance `t` along the geodesic.
+    pub fn at(&self, geometry: Geometry, t: f32) -> Vec4 {
+        geometry.cosine(t) * self.position + geometry.sine(t) * self.tangent
+    }
+}
+
+impl Geometry {
+    /// The bilinear form of the model. For hyperbolic space this is the
+    /// Minkowski inner product.
+    pub fn inner(&self, a: &Vec4, b: &Vec4) -> f32 {
+        match self {
+            Geometry::Euclidean | Geometry::Spherical => a.dot(b),
+            Geometry::Hyperbolic => a.x * b.x + a.y * b.y + a.z * b.z - a.w * b.w,
+        }
+    }
+
+    /// The function playing the part of `cos` for the geometry: a geodesic
+    /// leaving `P` with tangent `T` reaches `cosine(t) P + sine(t) T`.
+    pub fn cosine(&self, t: f32) -> f32 {
+        match self {
+            Geometry::Euclidean => 1.0,
+            Geometry::Hyperbolic => t.cosh(),
+            Geometry::Spherical => t.cos(),
+        }
+    }
+
+    /// The function playing the part of `sin` for the geometry. The sphere of
+    /// radius `t` has area `4 pi sine(t)^2`.
+    pub fn sine(&self, t: f32) -> f32 {
+        match self {
+            Geometry::Euclidean => t,
+            Geometry::Hyperbolic => t.sinh(),
+            Geometry::Spherical => t.sin(),
+        }
+    }
+
+    /// The area of a sphere of the given radius.
+    pub fn sphere_area(&self, radius: f32) -> f32 {
+        let sine = self.sine(radius);
+        4.0 * std::f32::consts::PI * sine * sine
+    }
+
+    /// Lift a point of the chart onto the model. Ideal points on the sphere
+    /// at infinity of hyperbolic space lift to the light cone instead.
+    pub fn lift(&self, point: Vec3) -> Vec4 {
+        match self {
+            Geometry::Euclidean => Vec4::point(point),
+            Geometry::Hyperbolic => {
+                let norm = 1.0 - point.length_sq();
+                if norm < 1e-6 {
+                    Vec4::point(point)
+                } else {
+                    Vec4::point(point) / norm.sqrt()
+                }
+            }
+            Geometry::Spherical => Vec4::point(point) / (1.0 + point.length_sq()).sqrt(),
+        }
+    }
+
+    /// The unit vector of the model normal to a plane of the chart, pointing
+    /// to the side the plane's normal points to. In hyperbolic space this is
+    /// the pole of the plane: the plane is the set of `X` with `<X, N> = 0`.
+    pub fn plane_pole(&self, plane: &Plane) -> Vec4 {
+        match self {
+            Geometry::Euclidean => Vec4::direction(plane.normal.normalized()),
+            Geometry::Hyperbolic => {
+                let pole = Vec4::new(plane.normal.x, plane.normal.y, plane.normal.z, plane.offset);
+                pole / self.inner(&pole, &pole).max(1e-12).sqrt()
+            }
+            Geometry::Spherical => {
+                let pole = Vec4::new(
+                    plane.normal.x,
+                    plane.normal.y,
+                    plane.normal.z,
+                    -plane.offset,
+                );
+                pole / pole.length()
+            }
+        }
+    }
+
+    /// The geodesic distance between two points of the chart.
+    pub fn distance(&self, a: Vec3, b: Vec3) -> f32 {
+        match self {
+            Geometry::Euclidean => (a - b).length(),
+            Geometry::Hyperbolic => {
+                let cosh = -self.inner(&self.lift(a), &self.lift(b));
+                cosh.max(1.0).acosh()
+            }
+            Geometry::Spherical => {
+                let cos = self.inner(&self.lift(a), &self.lift(b));
+                cos.clamp(-1.0, 1.0).acos()
+            }
+        }
+    }
+
+    /// The distance from a point of the chart to a plane of the chart.
+    pub fn plane_distance(&self, point: Vec3, plane: &Plane) -> f32 {
+        let along = || self.inner(&self.lift(point), &self.plane_pole(plane)).abs();
+        match self {
+            Geometry::Euclidean => plane.signed_distance(point).abs() / plane.normal.length(),
+            Geometry::Hyperbolic => along().asinh(),
+            Geometry::Spherical => along().min(1.0).asin(),
+        }
+    }
+
+    /// The point of the chart reached by walking from the origin along the
+    /// geodesic in the direction of `vector`, for the length of `vector`.
+    pub fn exponential(&self, vector: Vec3) -> Vec3 {
+        let length = vector.length();
+        if length < 1e-6 {
+            return vector;
+        }
+        match self {
+            Geometry::Euclidean => vector,
+            Geometry::Hyperbolic => vector * (length.tanh() / length),
+            Geometry::Spherical => vector * (length.tan() / length),
+        }
+    }
+
+    /// The isometry which slides the origin of the chart along a geodesic to
+    /// `point` without rotating.
+    pub fn translation(&self, point: Vec3) -> Mat4 {
+        match self {
+            Geometry::Euclidean => Mat4::translation(point),
+            Geometry::Hyperbolic => {
+                let lifted = self.lift(point);
+                let p = lifted.xyz();
+                let scale = 1.0 / (1.0 + lifted.w);
+                Mat4::from_rows([
+                    [
+                        1.0 + scale * p.x * p.x,
+                        scale * p.x * p.y,
+                        scale * p.x * p.z,
+                        p.x,
+                    ],
+                    [
+                        scale * p.y * p.x,
+                        1.0 + scale * p.y * p.y,
+                        scale * p.y * p.z,
+                        p.y,
+                    ],
+                    [
+                        scale * p.z * p.x,
+                        scale * p.z * p.y,
+                        1.0 + scale * p.z * p.z,
+                        p.z,
+                    ],
+                    [p.x, p.y, p.z, lifted.w],
+                ])
+            }
+            // The rotation of the plane spanned by the origin and `point`.
+            Geometry::Spherical => {
+                let lifted = self.lift(point);
+                let p = lifted.xyz();
+                let scale = 1.0 / (1.0 + lifted.w);
+                Mat4::from_rows([
+                    [
+                        1.0 - scale * p.x * p.x,
+                        -scale * p.x * p.y,
+                        -scale * p.x * p.z,
+                        p.x,
+                    ],
+                    [
+                        -scale * p.y * p.x,
+                        1.0 - scale * p.y * p.y,
+                        -scale * p.y * p.z,
+                        p.y,
+                    ],
+                    [
+                        -scale * p.z * p.x,
+                        -scale * p.z * p.y,
+                        1.0 - scale * p.z * p.z,
+                        p.z,
+                    ],
+                    [-p.x, -p.y, -p.z, lifted.w],
+                ])
+            }
+        }
+    }
+
+    /// The isometry which mirrors space across a plane of the chart.
+    pub fn reflection(&self, plane: &Plane) -> Mat4 {
+        let pole = self.plane_pole(plane);
+        // How far a vector of the model sits along the pole, which is zero
+        // exactly on the plane.
+        let height = |vector: Vec4| match self {
+            Geometry::Euclidean => {
+                (vector.xyz().dot(&plane.normal) - plane.offset * vector.w) / plane.normal.length()
+            }
+            Geometry::Hyperbolic | Geometry::Spherical => self.inner(&vector, &pole),
+        };
+        let reflect = |vector: Vec4| vector - 2.0 * height(vector) * pole;
+        Mat4::from_columns([
+            reflect(Vec4::new(1.0, 0.0, 0.0, 0.0)),
+            reflect(Vec4::new(0.0, 1.0, 0.0, 0.0)),
+            reflect(Vec4::new(0.0, 0.0, 1.0, 0.0)),
+            reflect(Vec4::new(0.0, 0.0, 0.0, 1.0)),
+        ])
+    }
+
+    /// The inverse of `translation`, bringing `point` back to the origin.
+    pub fn translation_inverse(&self, point: Vec3) -> Mat4 {
+        match self {
+            Geometry::Euclidean => Mat4::translation(-point),
+            // Boosts and rotations are undone by going the other way.
+            Geometry::Hyperbolic | Geometry::Spherical => self.translation(-point),
+        }
+    }
+
+    /// The geodesic followed by a ray, in the model.
+    pub fn geodesic(&self, ray: &Ray) -> Geodesic {
+        let position = self.lift(ray.origin);
+        let direction = Vec4::direction(ray.direction);
+        let tangent = match self {
+            Geometry::Euclidean => direction,
+            // Project onto the tangent space at `position`.
+            Geometry::Hyperbolic => direction + self.inner(&direction, &position) * position,
+            Geometry::Spherical => direction - self.inner(&direction, &position) * position,
+        };
+        let length = self.inner(&tangent, &tangent).max(1e-12).sqrt();
+        Geodesic {
+            position,
+            tangent: tangent / length,
+        }
+    }
+
+    /// The parameter along a ray (in chart units) at which it passes through
+    /// the given point of the model.
+    pub fn chart_parameter(&self, ray: &Ray, point: Vec4) -> f32 {
+        (point.dehomogenize() - ray.origin).dot(&ray.direction) / ray.direction.length_sq()
+    }
+
+    /// The parameter along a ray (in chart units) at which it has travelled
+    /// `distance` along its geodesic.
+    pub fn parameter_at_distance(&self, ray: &Ray, distance: f32) -> f32 {
+        match self {
+            Geometry::Euclidean => distance / ray.direction.length(),
+            _ => self.chart_parameter(ray, self.geodesic(ray).at(*self, distance)),
+        }
+    }
+
+    /// How far along a ray (in chart units) it can travel before leaving the
+    /// chart altogether.
+    pub fn chart_extent(&self, ray: &Ray) -> f32 {
+        match self {
+            Geometry::Euclidean | Geometry::Spherical => f32::MAX,
+            Geometry::Hyperbolic => {
+                // Solve |origin + t * direction| = 1 for the far root.
+                let a = ray.direction.length_sq();
+                let b = ray.origin.dot(&ray.direction);
+                let c = ray.origin.length_sq() - 1.0;
+                let descriminant = (b * b - a * c).max(0.0);
+                (-b + descriminant.sqrt()) / a
+            }
+        }
+    }
+
+    /// Express a direction at `point` of the chart in the orthonormal frame
+    /// obtained by translating the frame at the origin out to `point`.
+    pub fn tangent_to_local(&self, point: Vec3, vector: Vec3) -> Vec3 {
+        if *self == Geometry::Euclidean {
+            return vector;
+        }
+        let ray = Ray::new(point, vector);
+        self.translation_inverse(point)
+            .transform_ray(&ray)
+            .direction
+    }
+
+    /// The inverse of `tangent_to_local`.
+    pub fn tangent_from_local(&self, point: Vec3, vector: Vec3) -> Vec3 {
+        if *self == Geometry::Euclidean {
+            return vector;
+        }
+        let ray = Ray::new(Vec3::zero(), vector);
+        self.translation(point).transform_ray(&ray).direction
+    }
+
+    /// Express the normal of a surface through `point` in the local frame at
+    /// `point`. Normals are dual to tangent vectors, so they transform by the
+    /// inverse transpose of the map tangent vectors do.
+    pub fn normal_to_local(&self, point: Vec3, normal: Vec3) -> Vec3 {
+        if *self == Geometry::Euclidean {
+            return normal;
+        }
+        let plane = Vec4::new(normal.x, normal.y, normal.z, -normal.dot(&point));
+        (self.translation(point).transpose() * plane)
+            .xyz()
+            .normalized()
+    }
+}
@@ -0,0 +1,334 @@
+use crate::math::ray::Ray;
+use crate::math::vectors::Vec3;
+
+/// The homogeneous geometries which are not isotropic, so have no chart in
+/// which geodesics are straight. Rays in these are followed by integrating
+/// the geodesic equation rather than solved for in closed form.
+///
+/// Each is drawn in global coordinates `(x, y, z)` in which it has a simple
+/// orthonormal coframe, given as the rows of a matrix `F`:
+///
+/// - `Nil`: `dx`, `dy`, `dz + (y dx - x dy) / 2`.
+/// - `Sol`: `e^z dx`, `e^-z dy`, `dz`.
+/// - `SL2R`: the universal cover of SL(2, R), a twisted version of H^2 x R
+///   with `dx`, `e^x dy`, `dz + e^x dy`.
+/// - `H2xR`: horocyclic coordinates on the hyperbolic plane, `dx`, `e^x dy`,
+///   times the line, `dz`.
+/// - `S2xR`: stereographic coordinates on the unit sphere, `s dx`, `s dy`
+///   with `s = 2 / (1 + x^2 + y^2)`, times the line, `dz`.
+///
+/// Directions (of rays, normals and so on) are given in the orthonormal
+/// frame dual to the coframe at the point they are attached to, so that
+/// materials can scatter in them as if space were flat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThurstonGeometry {
+    Nil,
+    Sol,
+    SL2R,
+    H2xR,
+    S2xR,
+}
+
+/// A point of a geodesic together with its velocity in coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct GeodesicState {
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+/// A 3x3 matrix given by its rows.
+type Rows = [Vec3; 3];
+
+fn apply(rows: &Rows, v: Vec3) -> Vec3 {
+    Vec3::new(rows[0].dot(&v), rows[1].dot(&v), rows[2].dot(&v))
+}
+
+impl ThurstonGeometry {
+    /// The orthonormal coframe at a point.
+    pub fn coframe(&self, p: Vec3) -> Rows {
+        use ThurstonGeometry::*;
+        match self {
+            Nil => [
+                Vec3::right(),
+                Vec3::up(),
+                Vec3::new(0.5 * p.y, -0.5 * p.x, 1.0),
+            ],
+            Sol => [
+                Vec3::new(p.z.exp(), 0.0, 0.0),
+                Vec3::new(0.0, (-p.z).exp(), 0.0),
+                Vec3::forward(),
+            ],
+            SL2R => [
+                Vec3::right(),
+                Vec3::new(0.0, p.x.exp(), 0.0),
+                Vec3::new(0.0, p.x.exp(), 1.0),
+            ],
+            H2xR => [
+                Vec3::right(),
+                Vec3::new(0.0, p.x.exp(), 0.0),
+                Vec3::forward(),
+            ],
+            S2xR => {
+                let s = 2.0 / (1.0 + p.x * p.x + p.y * p.y);
+                [
+                    Vec3::new(s, 0.0, 0.0),
+                    Vec3::new(0.0, s, 0.0),
+                    Vec3::forward(),
+                ]
+            }
+        }
+    }
+
+    /// The derivatives of the coframe along the x, y and z axes.
+    fn coframe_derivatives(&self, p: Vec3) -> [Rows; 3] {
+        use ThurstonGeometry::*;
+        let zero = [Vec3::zero(); 3];
+        match self {
+            Nil => [
+                [Vec3::zero(), Vec3::zero(), Vec3::new(0.0, -0.5, 0.0)],
+                [Vec3::zero(), Vec3::zero(), Vec3::new(0.5, 0.0, 0.0)],
+                zero,
+            ],
+            Sol => [
+                zero,
+                zero,
+                [
+                    Vec3::new(p.z.exp(), 0.0, 0.0),
+                    Vec3::new(0.0, -(-p.z).exp(), 0.0),
+                    Vec3::zero(),
+                ],
+            ],
+            SL2R => [
+                [
+                    Vec3::zero(),
+                    Vec3::new(0.0, p.x.exp(), 0.0),
+                    Vec3::new(0.0, p.x.exp(), 0.0),
+                ],
+                zero,
+                zero,
+            ],
+            H2xR => [
+                [Vec3::zero(), Vec3::new(0.0, p.x.exp(), 0.0), Vec3::zero()],
+                zero,
+                zero,
+            ],
+            S2xR => {
+                let s = 2.0 / (1.0 + p.x * p.x + p.y * p.y);
+                let ds_dx = -p.x * s * s;
+                let ds_dy = -p.y * s * s;
+                [
+                    [
+                        Vec3::new(ds_dx, 0.0, 0.0),
+                        Vec3::new(0.0, ds_dx, 0.0),
+                        Vec3::zero(),
+                    ],
+                    [
+                        Vec3::new(ds_dy, 0.0, 0.0),
+                        Vec3::new(0.0, ds_dy, 0.0),
+                        Vec3::zero(),
+                    ],
+                    zero,
+                ]
+            }
+        }
+    }
+
+    /// The orthonormal frame at a point, in coordinates. These are the
+    /// columns of the inverse of the coframe.
+    pub fn frame(&self, p: Vec3) -> Rows {
+        let [r0, r1, r2] = self.coframe(p);
+        let columns = [r1.cross(&r2), r2.cross(&r0), r0.cross(&r1)];
+        let det = r0.dot(&columns[0]);
+        [columns[0] / det, columns[1] / det, columns[2] / det]
+    }
+
+    /// Write a coordinate velocity at `p` in the orthonormal frame.
+    pub fn tangent_to_frame(&self, p: Vec3, velocity: Vec3) -> Vec3 {
+        apply(&self.coframe(p), velocity)
+    }
+
+    /// The coordinate velocity with components `direction` in the
+    /// orthonormal frame at `p`.
+    pub fn tangent_from_frame(&self, p: Vec3, direction: Vec3) -> Vec3 {
+        let frame = self.frame(p);
+        direction.x * frame[0] + direction.y * frame[1] + direction.z * frame[2]
+    }
+
+    /// Write the gradient of a function at `p` (a covector in coordinates)
+    /// as a vector in the orthonormal frame.
+    pub fn gradient_to_frame(&self, p: Vec3, gradient: Vec3) -> Vec3 {
+        let frame = self.frame(p);
+        Vec3::new(
+            gradient.dot(&frame[0]),
+            gradient.dot(&frame[1]),
+            gradient.dot(&frame[2]),
+        )
+    }
+
+    /// The acceleration of a geodesic passing through `p` with coordinate
+    /// velocity `v`. With `w = F v` the geodesic equation for the metric
+    /// `F^T F` reads `F^T (F a + D v) = r - D^T w`, where `D` is the
+    /// derivative of `F` along `v` and `r_i = (d_i F v) . w`.
+    pub fn acceleration(&self, p: Vec3, v: Vec3) -> Vec3 {
+        let coframe = self.coframe(p);
+        let derivatives = self.coframe_derivatives(p);
+        let w = apply(&coframe, v);
+        let mut along = [Vec3::zero(); 3];
+        for (axis, derivative) in derivatives.iter().enumerate() {
+            for row in 0..3 {
+                along[row] += v[axis] * derivative[row];
+            }
+        }
+        let r = Vec3::new(
+            apply(&derivatives[0], v).dot(&w),
+            apply(&derivatives[1], v).dot(&w),
+            apply(&derivatives[2], v).dot(&w),
+        );
+        let z = r - (w.x * along[0] + w.y * along[1] + w.z * along[2]);
+        let y = self.gradient_to_frame(p, z) - apply(&along, v);
+        self.tangent_from_frame(p, y)
+    }
+
+    fn derivative(&self, state: &GeodesicState) -> GeodesicState {
+        GeodesicState {
+            position: state.velocity,
+            velocity: self.acceleration(state.position, state.velocity),
+        }
+    }
+
+    /// Follow a geodesic for a distance `h` with a single Runge-Kutta step.
+    pub fn step(&self, state: &GeodesicState, h: f32) -> GeodesicState {
+        let offset = |k: &GeodesicState, scale: f32| GeodesicState {
+            position: state.position + scale * k.position,
+            velocity: state.velocity + scale * k.velocity,
+        };
+        let k1 = self.derivative(state);
+        let k2 = self.derivative(&offset(&k1, 0.5 * h));
+        let k3 = self.derivative(&offset(&k2, 0.5 * h));
+        let k4 = self.derivative(&offset(&k3, h));
+        GeodesicState {
+            position: state.position
+                + (h / 6.0) * (k1.position + 2.0 * k2.position + 2.0 * k3.position + k4.position),
+            velocity: state.velocity
+                + (h / 6.0) * (k1.velocity + 2.0 * k2.velocity + 2.0 * k3.velocity + k4.velocity),
+        }
+    }
+}
+
+/// What a collider reports when a piece of geodesic crosses it.
+#[derive(Clone, Copy, Debug)]
+pub struct MarchHit {
+    /// How far along the piece the crossing is.
+    pub distance: f32,
+    /// The gradient, in coordinates, of a function which vanishes on the
+    /// surface and increases in the direction the surface faces.
+    pub gradient: Vec3,
+    pub u: f32,
+    pub v: f32,
+}
+
+/// A piece of a geodesic, parametrized by arc length from 0 to `length`.
+pub type GeodesicPiece<'a> = &'a dyn Fn(f32) -> Vec3;
+
+/// Things which can find where a piece of a curved path crosses them, for
+/// rays which do not travel in straight lines.
+pub trait RayMarchable {
+    fn march_hit(&self, piece: GeodesicPiece, length: f32, cast_time: f32) -> Option<MarchHit>;
+}
+
+/// Find where `level` changes sign along a piece of geodesic, by bisection.
+/// Only finds a crossing if the signs at the two ends differ, so a surface
+/// dipped into and out of within one piece is missed.
+pub fn bisect<F: Fn(Vec3) -> f32>(piece: GeodesicPiece, length: f32, level: F) -> Option<f32> {
+    let start = level(piece(0.0));
+    let end = level(piece(length));
+    if start == 0.0 || start.signum() == end.signum() {
+        return None;
+    }
+    let (mut low, mut high) = (0.0, length);
+    for _ in 0..24 {
+        let middle = 0.5 * (low + high);
+        if level(piece(middle)).signum() == start.signum() {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    Some(0.5 * (low + high))
+}
+
+/// How far from the origin, in coordinates, a ray may get before it is
+/// treated as escaping.
+const ESCAPE_RADIUS: f32 = 1e3;
+
+/// Follows rays through a Thurston geometry with an adaptive Runge-Kutta
+/// integrator. Each step is taken twice, once whole and once in two halves,
+/// and shortened until the two agree to within `tolerance`.
+#[derive(Clone, Copy, Debug)]
+pub struct GeodesicMarcher {
+    pub geometry: ThurstonGeometry,
+    pub tolerance: f32,
+    /// The longest step taken. Surfaces thinner than this may be stepped
+    /// over without being seen.
+    pub max_step: f32,
+    /// How far rays are followed before giving up on them.
+    pub max_distance: f32,
+}
+
+impl GeodesicMarcher {
+    pub fn new(geometry: ThurstonGeometry) -> GeodesicMarcher {
+        GeodesicMarcher {
+            geometry,
+            tolerance: 1e-4,
+            max_step: 0.1,
+            max_distance: 20.0,
+        }
+    }
+
+    /// Follow `ray`, whose direction is given in the orthonormal frame at
+    /// its origin, calling `visit` with the start of each step, the distance
+    /// travelled before it and its length. The rest of the step is found by
+    /// `ThurstonGeometry::step` from its start. Stops at the first step
+    /// `visit` returns something for, or after `t_max` (or `max_distance`).
+    pub fn march<T, F>(&self, ray: &Ray, t_max: f32, mut visit: F) -> Option<T>
+    where
+        F: FnMut(&GeodesicState, f32, f32) -> Option<T>,
+    {
+        let geometry = self.geometry;
+        let mut state = GeodesicState {
+            position: ray.origin,
+            velocity: geometry.tangent_from_frame(ray.origin, ray.direction.normalized()),
+        };
+        let t_max = t_max.min(self.max_distance);
+        let mut travelled = 0.0;
+        let mut h = self.max_step;
+        while travelled < t_max {
+            h = h.min(t_max - travelled);
+            let whole = geometry.step(&state, h);
+            let half = geometry.step(&geometry.step(&state, 0.5 * h), 0.5 * h);
+            // Coordinates can grow quickly (exponentially in Sol, and near
+            // the point at infinity of the S^2 x R chart), so the error is
+            // measured relative to the size of the state.
+            let scale = 1.0 + state.position.length() + state.velocity.length();
+            let error = ((whole.position - half.position).length()
+                + (whole.velocity - half.velocity).length())
+                / scale;
+            if error > self.tolerance && h > 1e-4 {
+                h *= 0.5;
+                continue;
+            }
+            if let Some(found) = visit(&state, travelled, h) {
+                return Some(found);
+            }
+            state = whole;
+            travelled += h;
+            // Nothing is placed this far out, and the coordinates are about
+            // to stop being any use.
+            if state.position.length() > ESCAPE_RADIUS {
+                return None;
+            }
+            h = (1.5 * h).min(self.max_step);
+        }
+        None
+    }
+}
@@ -65,7 +65,7 @@ macro_rules! op_impl {
                     $axis_one: self.$axis_one.$op_name(rhs.$axis_one),
                     $axis_two: self.$axis_two.$op_name(rhs.$axis_two),
                     $axis_three: self.$axis_three.$op_name(rhs.$axis_three),
-                    $axis_four: self.$axis_three.$op_name(rhs.$axis_four),
+                    $axis_four: self.$axis_four.$op_name(rhs.$axis_four),
                 }
             }
         }
@@ -77,7 +77,7 @@ macro_rules! op_impl {
                     $axis_one: self.$axis_one.$op_name(rhs.$axis_one),
                     $axis_two: self.$axis_two.$op_name(rhs.$axis_two),
                     $axis_three: self.$axis_three.$op_name(rhs.$axis_three),
-                    $axis_four: self.$axis_three.$op_name(rhs.$axis_four),
+                    $axis_four: self.$axis_four.$op_name(rhs.$axis_four),
                 }
             }
         }
@@ -89,7 +89,7 @@ macro_rules! op_impl {
                     $axis_one: self.$axis_one.$op_name(rhs.$axis_one),
                     $axis_two: self.$axis_two.$op_name(rhs.$axis_two),
                     $axis_three: self.$axis_three.$op_name(rhs.$axis_three),
-                    $axis_four: self.$axis_three.$op_name(rhs.$axis_four),
+                    $axis_four: self.$axis_four.$op_name(rhs.$axis_four),
                 }
             }
         }
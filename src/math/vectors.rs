@@ -159,9 +159,98 @@ impl From<[f32; 3]> for Vec3 {
     }
 }
 
+/// A four dimensional vector. Used for homogeneous coordinates and for the
+/// models of curved space which live in four dimensions.
+#[derive(Clone, Copy, Debug)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+op_impl!(Vec4, Add, add, x, y, z, w);
+op_assign_impl!(Vec4, AddAssign, add_assign, x, y, z, w);
+op_impl!(Vec4, Sub, sub, x, y, z, w);
+op_assign_impl!(Vec4, SubAssign, sub_assign, x, y, z, w);
+op_scalar_impl!(Vec4, f32, Mul, mul, x, y, z, w);
+op_scalar_assign_impl!(Vec4, f32, MulAssign, mul_assign, x, y, z, w);
+op_scalar_impl!(Vec4, f32, Div, div, x, y, z, w);
+op_scalar_assign_impl!(Vec4, f32, DivAssign, div_assign, x, y, z, w);
+
+impl Vec4 {
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Vec4 {
+        Vec4 { x, y, z, w }
+    }
+
+    /// The homogeneous coordinates of a point.
+    pub fn point(point: Vec3) -> Vec4 {
+        Vec4::new(point.x, point.y, point.z, 1.0)
+    }
+
+    /// The homogeneous coordinates of a direction (a point at infinity).
+    pub fn direction(direction: Vec3) -> Vec4 {
+        Vec4::new(direction.x, direction.y, direction.z, 0.0)
+    }
+
+    pub fn zero() -> Vec4 {
+        Vec4::new(0.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn dot(&self, other: &Vec4) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(&self) -> f32 {
+        f32::sqrt(self.dot(self))
+    }
+
+    pub fn xyz(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    /// Divide through by `w` to get back to a point in three dimensions.
+    pub fn dehomogenize(&self) -> Vec3 {
+        self.xyz() / self.w
+    }
+}
+
+impl Index<usize> for Vec4 {
+    type Output = f32;
+    fn index(&self, index: usize) -> &f32 {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            3 => &self.w,
+            _ => {
+                panic!("Invalid index for vector!");
+            }
+        }
+    }
+}
+
+impl Neg for Vec4 {
+    type Output = Vec4;
+    fn neg(self) -> Vec4 {
+        Vec4 {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+            w: -self.w,
+        }
+    }
+}
+
 use std::fmt;
 impl fmt::Display for Vec3 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "<{}, {}, {}>", self.x, self.y, self.z)
     }
 }
+
+impl fmt::Display for Vec4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<{}, {}, {}, {}>", self.x, self.y, self.z, self.w)
+    }
+}
@@ -123,6 +123,9 @@ impl BoundingVolumeHierarchy {
         t_min: f32,
         t_max: f32,
     ) -> Option<(RayHit, Material)> {
+        if self.hierarchy_heap.is_empty() {
+            return None;
+        }
         self.cast_ray_impl(ref_renderables, 0, ray, t_min, t_max)
     }
 
@@ -1,10 +1,14 @@
+use crate::math::manifold::complex::CellComplex;
+use crate::math::matrix::Mat4;
 use crate::math::quaternion::Quaternion;
 use crate::math::ray::Ray;
-use crate::math::vectors::Vec3;
+use crate::math::space::Geometry;
+use crate::math::vectors::{Vec3, Vec4};
 
 use rand::distributions::Uniform;
 use rand::{thread_rng, Rng};
 
+#[derive(Clone)]
 pub struct Camera {
     pub location: Vec3,
     pub extents: Vec3,
@@ -13,8 +17,20 @@ pub struct Camera {
     pub forward: Vec3,
     pub lens_radius: f32,
     pub orientation: Quaternion,
+    /// In curved geometries the frame above is the one at the origin of the
+    /// chart, which is then carried out to `location`.
+    pub geometry: Geometry,
+    /// The cell of the manifold the camera is in, whose chart `location` is
+    /// a point of.
+    pub chart: usize,
+    /// Whether the camera has been carried through a gluing which reverses
+    /// orientation, leaving its frame left handed.
+    pub mirrored: bool,
 }
 
+/// How many gluings the camera may be carried across in a single move.
+const MAX_CROSSINGS: usize = 64;
+
 fn random_point_on_disk() -> Vec3 {
     let mut rng = thread_rng();
     let between = Uniform::new(-1.0f32, 1.0f32);
@@ -56,19 +72,138 @@ impl Camera {
             forward: rotation * Vec3::new(0.0, 0.0, 1.0) * focus_dist,
             lens_radius: aperture / 2.0,
             orientation: rotation,
+            geometry: Geometry::Euclidean,
+            chart: 0,
+            mirrored: false,
+        }
+    }
+
+    /// Start the camera off in `chart` of a manifold.
+    pub fn in_chart(self, chart: usize) -> Camera {
+        Camera { chart, ..self }
+    }
+
+    fn with_orientation(self, rotation: Quaternion, mirrored: bool) -> Camera {
+        let side = if mirrored { -1.0 } else { 1.0 };
+        Camera {
+            horizontal: 2.0 * (rotation * Vec3::new(side * self.extents.x, 0.0, 0.0)),
+            vertical: 2.0 * (rotation * Vec3::new(0.0, self.extents.y, 0.0)),
+            forward: rotation * Vec3::new(0.0, 0.0, self.extents.z),
+            orientation: rotation,
+            mirrored,
+            ..self
         }
     }
 
+    /// Treat the camera as living in `geometry`. The location and target it
+    /// was created with are kept as points of that geometry's chart.
+    pub fn in_geometry(self, geometry: Geometry) -> Camera {
+        let forward = geometry.tangent_to_local(self.location, self.orientation * Vec3::forward());
+        let up = geometry.tangent_to_local(self.location, self.orientation * Vec3::up());
+        let mirrored = self.mirrored;
+        Camera { geometry, ..self }.with_orientation(Quaternion::look_at(forward, up), mirrored)
+    }
+
+    /// The isometry of the chart taking the origin and the frame there to
+    /// the camera and its frame (x right, y up and z forward).
+    pub fn frame(&self) -> Mat4 {
+        let frame = self
+            .geometry
+            .translation(self.location)
+            .compose(&Mat4::rotation(self.orientation));
+        if self.mirrored {
+            frame.compose(&Mat4::reflection(Vec3::right()))
+        } else {
+            frame
+        }
+    }
+
+    /// Move the camera to wherever `frame` takes the origin, facing the way
+    /// it takes the frame there.
+    fn with_frame(self, frame: Mat4) -> Camera {
+        let location = frame.transform_point(Vec3::zero());
+        let local = self.geometry.translation_inverse(location).compose(&frame);
+        // Points of the chart are only defined up to scale, so the frame
+        // may have come out negated.
+        let sign = local.rows[3][3].signum();
+        let axis = |axis: Vec3| (local * Vec4::direction(axis)).xyz() * sign;
+        let (right, up, forward) = (axis(Vec3::right()), axis(Vec3::up()), axis(Vec3::forward()));
+        let mirrored = right.cross(&up).dot(&forward) < 0.0;
+        let rotation = Quaternion::look_at(forward.normalized(), up.normalized());
+        Camera { location, ..self }.with_orientation(rotation, mirrored)
+    }
+
+    /// Walk the camera along the geodesic leaving it in the direction of
+    /// `displacement`, for the length of `displacement`. The displacement is
+    /// given in the camera's own frame, so `Vec3::forward()` always moves
+    /// the way it is looking. Inside a manifold the camera is carried across
+    /// every gluing it passes through, turning (or mirroring) its frame to
+    /// match. A move which would run into a face that is not glued to
+    /// anything leaves the camera where it was.
+    pub fn move_by(self, displacement: Vec3, manifold: Option<&CellComplex>) -> Camera {
+        let step = self
+            .geometry
+            .translation(self.geometry.exponential(displacement));
+        let mut frame = self.frame().compose(&step);
+        let manifold = match manifold {
+            Some(manifold) => manifold,
+            None => return self.with_frame(frame),
+        };
+        // Geodesics are straight lines in the chart, so follow the segment
+        // out to the new location, crossing faces along the way.
+        let mut chart = self.chart;
+        let mut start = self.location;
+        let mut entered = None;
+        for _ in 0..MAX_CROSSINGS {
+            let end = frame.transform_point(Vec3::zero());
+            let cell = &manifold.cells[chart];
+            if cell.contains(end) {
+                break;
+            }
+            let segment = Ray::look_at(start, end);
+            let (t, face) = match cell.exit(&segment, entered) {
+                Some(exit) => exit,
+                None => break,
+            };
+            let gluing = match cell.faces[face].gluing {
+                Some(gluing) => gluing,
+                None => return self,
+            };
+            frame = gluing.isometry.compose(&frame);
+            start = gluing
+                .isometry
+                .transform_point(segment.point_at_parameter(t));
+            chart = gluing.cell;
+            entered = Some(gluing.face);
+        }
+        Camera { chart, ..self }.with_frame(frame)
+    }
+
+    /// Turn the camera by `rotation`, given in the camera's own frame.
+    pub fn rotate_by(self, rotation: Quaternion) -> Camera {
+        let frame = self.frame().compose(&Mat4::rotation(rotation));
+        self.with_frame(frame)
+    }
+
     /// Convert a point in camera space into world space.
     pub fn world_ray(&self, u: f32, v: f32) -> Ray {
         let lens_point = self.lens_radius * random_point_on_disk();
-        let start = self.location + self.orientation * lens_point;
-        let end = self.location
-            + self.forward
+        let start = self.orientation * lens_point;
+        let end = self.forward
             + (u - 0.5f32) * self.horizontal
             // Flip vertical because (0, 0) represents the top left corner.
             + (0.5f32 - v) * self.vertical;
-        Ray::look_at(start, end)
+        let ray = Ray {
+            mirrored: self.mirrored,
+            ..Ray::look_at(start, end).in_geometry(self.geometry)
+        };
+        if self.geometry == Geometry::Euclidean {
+            return Ray {
+                origin: start + self.location,
+                ..ray
+            };
+        }
+        self.geometry.translation(self.location).transform_ray(&ray)
     }
 
     pub fn world_ray_end(&self, u: f32, v: f32) -> Vec3 {
@@ -5,6 +5,7 @@ use rand_distr::{Distribution, UnitBall, UnitSphere};
 use crate::math::colors::Color;
 use crate::math::quaternion::Quaternion;
 use crate::math::ray::{Ray, RayHit};
+use crate::math::space::Geometry;
 use crate::math::vectors::Vec3;
 
 use crate::rendering::textures::{TextureAtlas, TextureIndex};
@@ -55,6 +56,19 @@ pub fn schlick(cosine: f32, index_of_reflection: f32) -> f32 {
 }
 
 impl Material {
+    /// An emissive ball of the given radius giving off `power` in total. The
+    /// power is spread over the ball's surface, whose area depends on the
+    /// geometry, so the same lamp is dimmer per unit area in hyperbolic space
+    /// and brighter in spherical space.
+    pub fn lamp(texture: TextureIndex, power: f32, radius: f32, geometry: Geometry) -> Material {
+        Material::Emissive {
+            texture,
+            amplify: power / (std::f32::consts::PI * geometry.sphere_area(radius)),
+        }
+    }
+
+    /// Scattered rays keep the handedness of `ray`, unless the material is
+    /// itself a mirror of space.
     pub fn scatter<T: Rng>(
         &self,
         ray: &Ray,
@@ -67,16 +81,16 @@ impl Material {
         use Material::*;
         match self {
             &Lambertian { albedo } => {
-                *attenuation = atlas.evaluate(albedo, hit.u, hit.v, hit.location);
+                *attenuation = atlas.evaluate(albedo, hit);
                 let target = hit.location + hit.normal + Vec3::from(UnitBall.sample(rng));
-                Some(Ray::look_at(hit.location, target))
+                Some(Ray::look_at(hit.location, target).oriented_like(ray))
             }
             &Metal { albedo, fuzziness } => {
-                *attenuation = atlas.evaluate(albedo, hit.u, hit.v, hit.location);
+                *attenuation = atlas.evaluate(albedo, hit);
                 let reflected_direction = reflect(ray.direction, hit.normal);
                 let freedom = f32::min(1.0f32, fuzziness);
                 let offset: Vec3 = reflected_direction + Vec3::from(UnitBall.sample(rng)) * freedom;
-                let next_ray = Ray::look_at(hit.location, hit.location + offset);
+                let next_ray = Ray::look_at(hit.location, hit.location + offset).oriented_like(ray);
                 // Only cast a new ray if we notice we are sending it to the outside
                 // If it would bounce back in, we just stop... Is this desired behaviour?
                 // I do not know.
@@ -105,36 +119,48 @@ impl Material {
                     // And we roll that we should refract (based on our angle i.e. schlick)
                     if rng.sample(between) > schlick(cosine, index_of_refraction) {
                         // Then return that we should follow a ray off towards there
-                        return Some(Ray::look_at(hit.location, hit.location + offset));
+                        return Some(
+                            Ray::look_at(hit.location, hit.location + offset).oriented_like(ray),
+                        );
                     }
                 }
                 // In all other cases, we will reflect
                 let offset = reflect(ray.direction, hit.normal);
-                Some(Ray::look_at(hit.location, hit.location + offset))
+                Some(Ray::look_at(hit.location, hit.location + offset).oriented_like(ray))
             }
             &Emissive { .. } => None,
             &Isotropic { albedo } => {
-                *attenuation = atlas.evaluate(albedo, hit.u, hit.v, hit.location);
+                *attenuation = atlas.evaluate(albedo, hit);
                 let out_dir = UnitSphere.sample(rng).into();
-                Some(Ray::new(hit.location, out_dir))
+                Some(Ray::new(hit.location, out_dir).oriented_like(ray))
             }
             &Projective => {
                 *attenuation = Color::new(1.0, 1.0, 1.0);
-                Some(Ray::new(
-                    -hit.location,
-                    Quaternion::axis_angle(hit.normal, std::f32::consts::PI) * ray.direction,
-                ))
+                // Sending x to -x reverses orientation, and the half turn
+                // does not undo it.
+                Some(Ray {
+                    mirrored: !ray.mirrored,
+                    ..Ray::new(
+                        -hit.location,
+                        Quaternion::axis_angle(hit.normal, std::f32::consts::PI) * ray.direction,
+                    )
+                })
             }
             &DirectionCollapse => {
                 *attenuation = Color::new(1.0, 1.0, 1.0);
-                Some(Ray::new(
-                    -hit.location.length() * ray.direction,
-                    ray.direction,
-                ))
+                Some(
+                    Ray::new(-hit.location.length() * ray.direction, ray.direction)
+                        .oriented_like(ray),
+                )
             }
         }
     }
 
+    /// Emitted light is radiance, which does not fall off along a ray in
+    /// any geometry. Falloff comes from rays following geodesics: a light
+    /// covers exponentially less of the sky as it recedes in hyperbolic
+    /// space, and covers more again as it nears the antipode in spherical
+    /// space.
     pub fn emit<T: Rng>(
         &self,
         ray_hit: &RayHit,
@@ -144,9 +170,7 @@ impl Material {
     ) -> Color {
         use Material::*;
         match self {
-            &Emissive { texture, amplify } => {
-                amplify * atlas.evaluate(texture, ray_hit.u, ray_hit.v, ray_hit.location)
-            }
+            &Emissive { texture, amplify } => amplify * atlas.evaluate(texture, ray_hit),
             _ => Color::zero(),
         }
     }
@@ -1,6 +1,7 @@
 pub mod bvh;
 pub mod camera;
 pub mod materials;
+pub mod overlay;
 pub mod perlin;
 pub mod scene;
 pub mod textures;
@@ -0,0 +1,110 @@
+use crate::math::colors::Color;
+use crate::math::geometry::tube::TubeGeometry;
+use crate::math::manifold::complex::CellComplex;
+use crate::math::ray::{Ray, RayCollidable};
+use crate::rendering::scene::Scene;
+
+/// Colors the faces of each gluing class are tinted by, in turn.
+const PALETTE: [(f32, f32, f32); 8] = [
+    (0.9, 0.3, 0.3),
+    (0.3, 0.8, 0.3),
+    (0.3, 0.4, 0.9),
+    (0.9, 0.8, 0.2),
+    (0.8, 0.3, 0.8),
+    (0.2, 0.8, 0.8),
+    (0.9, 0.5, 0.1),
+    (0.6, 0.6, 0.6),
+];
+
+/// Shows how a manifold is cut into cells on top of a render, without adding
+/// anything to the scene. The edges of every cell glow and every face a ray
+/// passes through on its way from the camera tints what is behind it, with
+/// the two faces of a gluing sharing a tint. Rays wrap around through the
+/// gluings as usual, so every copy of the cells is outlined.
+pub struct SkeletonOverlay {
+    /// The edges of each cell, in its chart.
+    pub edges: Vec<Vec<TubeGeometry>>,
+    /// The tint of each face of each cell.
+    pub tints: Vec<Vec<Color>>,
+    pub edge_color: Color,
+    /// How strongly crossing a face tints what is seen through it, from 0
+    /// (not at all) to 1 (as if looking through colored glass).
+    pub opacity: f32,
+}
+
+impl SkeletonOverlay {
+    /// Outline the cells of `manifold` with edges of the given radius.
+    pub fn new(manifold: &CellComplex, radius: f32) -> SkeletonOverlay {
+        let edges = manifold
+            .cells
+            .iter()
+            .map(|cell| {
+                cell.edges()
+                    .into_iter()
+                    .map(|(start, end)| TubeGeometry::new(start, end, radius))
+                    .collect()
+            })
+            .collect();
+        let tints = manifold
+            .gluing_classes()
+            .iter()
+            .map(|classes| {
+                classes
+                    .iter()
+                    .map(|&class| {
+                        let (r, g, b) = PALETTE[class % PALETTE.len()];
+                        Color::new(r, g, b)
+                    })
+                    .collect()
+            })
+            .collect();
+        SkeletonOverlay {
+            edges,
+            tints,
+            edge_color: Color::new(1.0, 1.0, 1.0),
+            opacity: 0.15,
+        }
+    }
+
+    /// Lay the overlay over `color`, the color `scene` gave a ray which left
+    /// the camera in `chart`. The ray is followed through the cells again up
+    /// to the first thing it hits, picking up the tint of each face it
+    /// crosses and stopping at any edge in the way.
+    pub fn shade(&self, scene: &Scene, ray: &Ray, chart: usize, color: Color) -> Color {
+        let manifold = match scene.manifold {
+            Some(ref manifold) => manifold,
+            None => return color,
+        };
+        let mut ray = ray.in_geometry(scene.geometry);
+        let mut chart = chart;
+        let mut entered = None;
+        let mut filter = Color::new(1.0, 1.0, 1.0);
+        for _ in 0..=scene.max_crossings {
+            let exit = manifold.cells[chart].exit(&ray, entered);
+            let mut limit = exit.map_or(f32::MAX, |(t, _)| t);
+            if let Some((hit, _)) = scene.cast_in(chart, &ray, 0.0, limit) {
+                limit = hit.hit_fraction;
+            }
+            let edge = self.edges[chart]
+                .iter()
+                .any(|edge| edge.hit(&ray, 0.0, limit).is_some());
+            if edge {
+                return filter * self.edge_color;
+            }
+            let (t, face) = match exit {
+                Some(exit) if exit.0 <= limit => exit,
+                _ => break,
+            };
+            let tint = self.tints[chart][face];
+            filter *= Color::lerp(Color::new(1.0, 1.0, 1.0), tint, self.opacity);
+            let (gluing, next) = match manifold.cross(chart, face, &ray, t) {
+                Some(crossing) => crossing,
+                None => break,
+            };
+            ray = next;
+            chart = gluing.cell;
+            entered = Some(gluing.face);
+        }
+        filter * color
+    }
+}
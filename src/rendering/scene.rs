@@ -1,14 +1,33 @@
 use crate::math::colliders::Collider;
+use crate::math::manifold::complex::CellComplex;
+use crate::math::manifold::holonomy::Holonomy;
 use crate::math::ray::*;
+use crate::math::space::Geometry;
+use crate::math::thurston::{GeodesicMarcher, MarchHit, RayMarchable};
 
 use crate::rendering::bvh::BoundingVolumeHierarchy;
 use crate::rendering::materials::Material;
 use crate::rendering::textures::{Texture, TextureAtlas, TextureIndex};
 
 pub struct Scene {
+    pub charts: Vec<Chart>,
+    pub texture_atlas: TextureAtlas,
+    pub manifold: Option<CellComplex>,
+    /// The geometry every chart is drawn in.
+    pub geometry: Geometry,
+    /// How many gluings a single ray may cross before we give up on it.
+    pub max_crossings: u32,
+    /// Set to render in one of the Thurston geometries, where rays are
+    /// marched along curved paths instead of cast.
+    pub marcher: Option<GeodesicMarcher>,
+}
+
+/// The renderables living in a single coordinate chart. A flat scene has one
+/// chart, while a triangulated manifold has one chart per tetrahedron.
+#[derive(Default)]
+pub struct Chart {
     pub renderables: Vec<Renderable>,
     pub hierarchy: Option<BoundingVolumeHierarchy>,
-    pub texture_atlas: TextureAtlas,
 }
 
 pub struct Renderable {
@@ -16,56 +35,354 @@ pub struct Renderable {
     pub material: Material,
 }
 
-impl Scene {
+/// A hit found by following a ray through the scene. The ray and hit are both
+/// given in the local coordinates of `chart`, which may differ from the chart
+/// the ray started in if it crossed any gluings on the way.
+pub struct TracedHit {
+    pub ray: Ray,
+    pub chart: usize,
+    pub hit: RayHit,
+    pub material: Material,
+}
+
+impl Chart {
     pub fn new() -> Self {
-        Scene {
+        Chart {
             renderables: Vec::new(),
             hierarchy: None,
+        }
+    }
+
+    pub fn cast(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(RayHit, Material)> {
+        if let &Some(ref hierarchy) = &self.hierarchy {
+            hierarchy.cast_ray(&self.renderables, ray, t_min, t_max)
+        } else {
+            let mut best_hit: Option<(RayHit, Material)> = None;
+            let mut earliest_time = t_max;
+            for renderable in &self.renderables {
+                if let Some(hit) = renderable.collider.hit(&ray, t_min, earliest_time) {
+                    earliest_time = hit.hit_fraction;
+                    best_hit = Some((hit, renderable.material));
+                }
+            }
+            best_hit
+        }
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            charts: vec![Chart::new()],
             texture_atlas: TextureAtlas::new(),
+            manifold: None,
+            geometry: Geometry::Euclidean,
+            max_crossings: 64,
+            marcher: None,
         }
     }
 
+    /// Render the scene inside of `manifold`. Each cell (e.g. tetrahedron)
+    /// gets its own chart, and anything already placed with `put` lives in
+    /// cell 0. The scene takes on the geometry of the manifold.
+    pub fn set_manifold<M: Into<CellComplex>>(&mut self, manifold: M) {
+        let manifold = manifold.into();
+        self.geometry = manifold.geometry;
+        let num_charts = manifold.size().max(1);
+        self.charts.truncate(num_charts);
+        while self.charts.len() < num_charts {
+            self.charts.push(Chart::new());
+        }
+        self.manifold = Some(manifold);
+    }
+
     pub fn add_texture(&mut self, texture: Texture) -> TextureIndex {
         self.texture_atlas.add(texture)
     }
 
     pub fn put(&mut self, collider: Collider, material: Material) {
-        self.renderables.push(Renderable {
+        self.put_in(0, collider, material);
+    }
+
+    /// Place a collider in the local coordinates of the given chart.
+    pub fn put_in(&mut self, chart: usize, collider: Collider, material: Material) {
+        let chart = &mut self.charts[chart];
+        chart.renderables.push(Renderable {
             collider: collider,
             material: material,
         });
-        self.hierarchy = None;
+        chart.hierarchy = None;
     }
 
     pub fn compute_hierarchy(&mut self, t_min: f32, t_max: f32) {
-        self.hierarchy = Some(BoundingVolumeHierarchy::construct(
-            &self.renderables,
-            t_min,
-            t_max,
-        ));
+        // The gnomonic chart stretches distances, so bounding boxes computed
+        // as if space were flat are too small. Cast against everything.
+        if self.geometry == Geometry::Spherical {
+            return;
+        }
+        for chart in &mut self.charts {
+            // A chart with nothing bounded in it, like an empty cell of a
+            // manifold, has no hierarchy to build.
+            let bounded = chart
+                .renderables
+                .iter()
+                .any(|renderable| renderable.collider.bounding_box(t_min, t_max).is_some());
+            chart.hierarchy = if bounded {
+                Some(BoundingVolumeHierarchy::construct(
+                    &chart.renderables,
+                    t_min,
+                    t_max,
+                ))
+            } else {
+                None
+            };
+        }
     }
 
     pub fn print_hierarchy(&self) {
-        if let &Some(ref hierarchy) = &self.hierarchy {
-            hierarchy.print();
-        } else {
-            println!("None!");
+        for (index, chart) in self.charts.iter().enumerate() {
+            println!("Chart {}:", index);
+            if let &Some(ref hierarchy) = &chart.hierarchy {
+                hierarchy.print();
+            } else {
+                println!("None!");
+            }
         }
     }
 
     pub fn cast(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(RayHit, Material)> {
-        if let &Some(ref hierarchy) = &self.hierarchy {
-            hierarchy.cast_ray(&self.renderables, ray, t_min, t_max)
+        self.cast_in(0, ray, t_min, t_max)
+    }
+
+    /// Cast a ray given in the local coordinates of `chart` against only the
+    /// renderables placed in that chart.
+    pub fn cast_in(
+        &self,
+        chart: usize,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(RayHit, Material)> {
+        self.charts[chart].cast(ray, t_min, t_max)
+    }
+
+    /// Follow a ray starting in `chart` through the manifold until it hits
+    /// something. Whenever the ray leaves the current cell before hitting
+    /// anything it is carried across the gluing into the neighbouring cell.
+    /// Rays which leave through an unglued face, or which cross more than
+    /// `max_crossings` gluings, hit nothing.
+    pub fn trace(&self, ray: &Ray, chart: usize, t_min: f32, t_max: f32) -> Option<TracedHit> {
+        self.trace_from(ray, chart, Holonomy::new(), t_min, t_max)
+    }
+
+    /// Like `trace`, for a ray which has already crossed the gluings in
+    /// `holonomy` (say before scattering). The hit's holonomy carries on
+    /// from there.
+    pub fn trace_from(
+        &self,
+        ray: &Ray,
+        chart: usize,
+        holonomy: Holonomy,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<TracedHit> {
+        if let Some(ref marcher) = self.marcher {
+            return self.march(marcher, ray, chart, holonomy, t_min, t_max);
+        }
+        let ray = ray.in_geometry(self.geometry);
+        let manifold = if let Some(ref manifold) = self.manifold {
+            manifold
         } else {
-            let mut best_hit: Option<(RayHit, Material)> = None;
-            let mut earliest_time = t_max;
-            for renderable in &self.renderables {
-                if let Some(hit) = renderable.collider.hit(&ray, t_min, earliest_time) {
-                    earliest_time = hit.hit_fraction;
-                    best_hit = Some((hit, renderable.material));
+            // Nothing lies beyond the edge of the chart (the sphere at
+            // infinity for hyperbolic space).
+            let t_max = t_max.min(self.geometry.chart_extent(&ray));
+            return self
+                .cast_in(chart, &ray, t_min, t_max)
+                .map(|(hit, material)| TracedHit {
+                    ray,
+                    chart,
+                    hit: RayHit { holonomy, ..hit },
+                    material,
+                });
+        };
+        let mut ray = ray;
+        let mut chart = chart;
+        let mut holonomy = holonomy;
+        let mut entered = None;
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        // Chart parameters are not distances, and every crossing starts the
+        // ray over in a new chart, so what is left of `t_max` is carried
+        // across as a distance along the geodesic.
+        let geometry = self.geometry;
+        let mut remaining = if t_max < geometry.chart_extent(&ray) {
+            geometry.distance(ray.origin, ray.point_at_parameter(t_max))
+        } else {
+            f32::INFINITY
+        };
+        for _ in 0..=self.max_crossings {
+            let exit = manifold.cells[chart].exit(&ray, entered);
+            let limit = exit.map_or(t_max, |(t, _)| t.min(t_max));
+            if let Some((hit, material)) = self.cast_in(chart, &ray, t_min, limit) {
+                return Some(TracedHit {
+                    ray,
+                    chart,
+                    hit: RayHit { holonomy, ..hit },
+                    material,
+                });
+            }
+            let (t, face) = exit?;
+            if t >= t_max {
+                return None;
+            }
+            let (gluing, next_ray) = manifold.cross(chart, face, &ray, t)?;
+            holonomy.cross(chart, face, &gluing);
+            remaining -= geometry.distance(ray.origin, ray.point_at_parameter(t));
+            ray = next_ray;
+            chart = gluing.cell;
+            entered = Some(gluing.face);
+            t_min = 0.0;
+            t_max = if remaining.is_finite() {
+                geometry.parameter_at_distance(&ray, remaining.max(0.0))
+            } else {
+                f32::MAX
+            };
+        }
+        None
+    }
+
+    /// Follow a ray along its geodesic through the Thurston geometry of
+    /// `marcher`, testing each piece of it against every renderable of the
+    /// chart. The hit and the returned ray's direction are given in the
+    /// orthonormal frame at the hit. There are no gluings to cross, so the
+    /// hit's holonomy is `holonomy` as it was.
+    fn march(
+        &self,
+        marcher: &GeodesicMarcher,
+        ray: &Ray,
+        chart: usize,
+        holonomy: Holonomy,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<TracedHit> {
+        let geometry = marcher.geometry;
+        let renderables = &self.charts[chart].renderables;
+        marcher.march(ray, t_max, |start, travelled, length| {
+            // Every collider looks at both ends of the step, so only work
+            // those out once.
+            let end = geometry.step(start, length).position;
+            let piece = |s: f32| {
+                if s <= 0.0 {
+                    start.position
+                } else if s >= length {
+                    end
+                } else {
+                    geometry.step(start, s).position
+                }
+            };
+            let mut best: Option<(MarchHit, Material)> = None;
+            for renderable in renderables {
+                let hit = renderable
+                    .collider
+                    .march_hit(&piece, length, ray.cast_time)
+                    .filter(|hit| travelled + hit.distance >= t_min);
+                if let Some(hit) = hit {
+                    if best.is_none_or(|(best, _)| hit.distance < best.distance) {
+                        best = Some((hit, renderable.material));
+                    }
                 }
             }
-            best_hit
+            let (hit, material) = best?;
+            let end = geometry.step(start, hit.distance);
+            let location = end.position;
+            let direction = geometry
+                .tangent_to_frame(location, end.velocity)
+                .normalized();
+            Some(TracedHit {
+                ray: Ray {
+                    origin: location,
+                    direction,
+                    ..*ray
+                },
+                chart,
+                hit: RayHit {
+                    hit_fraction: travelled + hit.distance,
+                    location,
+                    normal: geometry
+                        .gradient_to_frame(location, hit.gradient)
+                        .normalized(),
+                    u: hit.u,
+                    v: hit.v,
+                    mirrored: ray.mirrored,
+                    holonomy: holonomy.clone(),
+                },
+                material,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loaders::regina;
+    use crate::math::colors::Color;
+    use crate::math::complex::Complex;
+    use crate::math::geometry::sphere::SphereGeometry;
+    use crate::math::manifold::ideal::IdealTriangulation;
+    use crate::math::manifold::identified_box::IdentifiedBox;
+    use crate::math::vectors::{Vec3, Vec4};
+
+    #[test]
+    fn empty_charts_trace_to_nothing() {
+        let mut scene = Scene::new();
+        scene.set_manifold(IdentifiedBox::three_torus(Vec3::all(1.0)));
+        scene.compute_hierarchy(0.0, 1.0);
+        let ray = Ray::new(Vec3::zero(), Vec3::new(1.0, 0.3, 0.2));
+        assert!(scene.trace(&ray, 0, 0.001, 100.0).is_none());
+    }
+
+    #[test]
+    fn distance_limit_carries_across_gluings() {
+        let combinatorics = regina::from_isosig("cPcbbbiht").unwrap();
+        let regular = Complex::from_polar(1.0, std::f32::consts::FRAC_PI_3);
+        let triangulation = IdealTriangulation::new(&combinatorics, vec![regular; 2]).triangulation;
+        let center = triangulation.tetrahedra[0].centroid();
+        let mut scene = Scene::new();
+        scene.set_manifold(&triangulation);
+        let material = Material::Lambertian {
+            albedo: TextureIndex::Constant(Color::new(0.5, 0.5, 0.5)),
+        };
+        scene.put_in(
+            1,
+            SphereGeometry::new(triangulation.tetrahedra[1].centroid(), 0.1).into(),
+            material,
+        );
+
+        let geometry = scene.geometry;
+        let mut checked = 0;
+        for index in 0..50 {
+            let angle = index as f32 * 2.4;
+            let direction = Vec3::new(angle.cos(), angle.sin(), 0.3 * (index as f32).sin());
+            let ray = Ray::new(center, direction).in_geometry(geometry);
+            let traced = match scene.trace(&ray, 0, 0.0, f32::MAX) {
+                Some(traced) => traced,
+                None => continue,
+            };
+            // Long paths run up the cusp, where the holonomy piles up error.
+            if !(2..8).contains(&traced.hit.holonomy.crossings) {
+                continue;
+            }
+            // Where the hit is in the universal cover, seen from the start.
+            let back = traced.hit.holonomy.isometry.inverse().unwrap();
+            let hit = (back * Vec4::point(traced.hit.location)).dehomogenize();
+            let distance = geometry.distance(center, hit);
+            let short = geometry.parameter_at_distance(&ray, 0.99 * distance);
+            let long = geometry.parameter_at_distance(&ray, 1.01 * distance);
+            assert!(scene.trace(&ray, 0, 0.0, short).is_none());
+            assert!(scene.trace(&ray, 0, 0.0, long).is_some());
+            checked += 1;
         }
+        assert!(checked > 0);
     }
 }
@@ -1,6 +1,7 @@
 use image::RgbImage;
 
 use crate::math::colors::Color;
+use crate::math::ray::RayHit;
 use crate::math::vectors::Vec3;
 
 use crate::rendering::perlin::Perlin;
@@ -33,7 +34,8 @@ impl TextureAtlas {
         }
     }
 
-    pub fn evaluate(&self, mut tex_index: TextureIndex, u: f32, v: f32, point: Vec3) -> Color {
+    pub fn evaluate(&self, mut tex_index: TextureIndex, hit: &RayHit) -> Color {
+        let (u, v, point) = (hit.u, hit.v, hit.location);
         loop {
             match tex_index {
                 TextureIndex::Constant(color) => {
@@ -69,6 +71,22 @@ impl TextureAtlas {
                                 tex_index = right;
                             }
                         }
+                        &Handedness(unmirrored, mirrored) => {
+                            tex_index = if hit.mirrored { mirrored } else { unmirrored };
+                        }
+                        Copies(textures) => {
+                            if textures.is_empty() {
+                                return Color::zero();
+                            }
+                            let copy = hit.holonomy.key() % textures.len() as u64;
+                            tex_index = textures[copy as usize];
+                        }
+                        WordLength(textures) => {
+                            if textures.is_empty() {
+                                return Color::zero();
+                            }
+                            tex_index = textures[hit.holonomy.length().min(textures.len() - 1)];
+                        }
                         Image(image, mode) => {
                             let x = f32::floor(image.width() as f32 * u);
                             let x = mode.adjust(x, image.width());
@@ -77,6 +95,14 @@ impl TextureAtlas {
                             let color_ = image.get_pixel(x, y);
                             return color_.into();
                         }
+                        VertexColors(layout, colors) => {
+                            if colors.is_empty() {
+                                return Color::zero();
+                            }
+                            let (triangle, s, r) = layout.locate(u, v);
+                            let [a, b, c] = colors[triangle.min(colors.len() - 1)];
+                            return a * (1.0 - s - r) + b * s + c * r;
+                        }
                         Perlin(scale) => {
                             return (0.5 * (1.0 + self.perlin.noise(scale * point))).into();
                         }
@@ -100,6 +126,67 @@ impl TextureAtlas {
     }
 }
 
+/// How much of each side of a triangle's cell is left empty, so rounding at
+/// the corners cannot reach into the next cell over.
+const CELL_MARGIN: f32 = 0.1;
+
+/// Gives each triangle of a mesh its own square cell of texture space, in
+/// rows from the bottom left. Giving the corners of each triangle the
+/// coordinates of three corners of its cell lets a texture work out which
+/// triangle was hit, and where on it, from the interpolated coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct TriangleLayout {
+    pub triangles: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl TriangleLayout {
+    pub fn new(triangles: usize) -> TriangleLayout {
+        let columns = ((triangles as f64).sqrt().ceil() as usize).max(1);
+        TriangleLayout {
+            triangles,
+            columns,
+            rows: triangles.div_ceil(columns).max(1),
+        }
+    }
+
+    /// The texture coordinates to give `corner` (0, 1 or 2) of `triangle`.
+    pub fn corner_uv(&self, triangle: usize, corner: usize) -> (f32, f32) {
+        let (s, r) = match corner {
+            0 => (0.0, 0.0),
+            1 => (1.0, 0.0),
+            _ => (0.0, 1.0),
+        };
+        let scale = 1.0 - 2.0 * CELL_MARGIN;
+        let column = (triangle % self.columns) as f32;
+        let row = (triangle / self.columns) as f32;
+        (
+            (column + CELL_MARGIN + s * scale) / self.columns as f32,
+            (row + CELL_MARGIN + r * scale) / self.rows as f32,
+        )
+    }
+
+    /// The triangle whose cell the texture coordinates are in, and the
+    /// barycentric weights of its second and third corners there.
+    pub fn locate(&self, u: f32, v: f32) -> (usize, f32, f32) {
+        let cell = |coordinate: f32, count: usize| {
+            let scaled = coordinate * count as f32;
+            let index = scaled.floor().clamp(0.0, count as f32 - 1.0);
+            let along = (scaled - index - CELL_MARGIN) / (1.0 - 2.0 * CELL_MARGIN);
+            (index as usize, along.clamp(0.0, 1.0))
+        };
+        let (column, s) = cell(u, self.columns);
+        let (row, r) = cell(v, self.rows);
+        let (s, r) = if s + r > 1.0 {
+            (s / (s + r), r / (s + r))
+        } else {
+            (s, r)
+        };
+        (row * self.columns + column, s, r)
+    }
+}
+
 pub enum SampleMode {
     Clamp,
     Wrap,
@@ -134,7 +221,19 @@ pub enum Texture {
     Constant(Color),
     CheckerVolume(TextureIndex, TextureIndex, f32),
     CheckerSurface(TextureIndex, TextureIndex, u32),
+    /// Use the first texture when a hit is seen directly and the second when
+    /// it is seen through an orientation reversing gluing.
+    Handedness(TextureIndex, TextureIndex),
+    /// Pick a texture for each copy of the fundamental domain, going by the
+    /// word of gluings the ray crossed to reach the hit.
+    Copies(Vec<TextureIndex>),
+    /// Pick a texture by how many generators are in that word, using the
+    /// last texture for every longer word.
+    WordLength(Vec<TextureIndex>),
     Image(RgbImage, SampleMode),
+    /// A color for each corner of each triangle of a mesh laid out with the
+    /// layout, blended across the triangle.
+    VertexColors(TriangleLayout, Vec<[Color; 3]>),
     Perlin(f32),
     Turbulence(f32, u32, f32),
     Noise(f32, u32, f32, Box<dyn Fn(Vec3, Vec3) -> Color>),